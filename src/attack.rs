@@ -0,0 +1,175 @@
+//! Punches and what became of them. A fighter announces each punch it throws
+//! with an [`AttackEvent`]; [`resolve_attacks`] then checks the opponent on
+//! every tick the punch is active and reports exactly one outcome:
+//! [`HitEvent`], [`BlockedEvent`] or, if the opponent slipped it,
+//! [`DodgedEvent`]. A punch that finds nothing to land on by the end of its
+//! window is a [`WhiffEvent`]. Anything that reacts to a punch (sound, effects, UI,
+//! scoring) should read those instead of looking at fighter state.
+
+use bevy::prelude::*;
+
+use crate::{
+    enemy::Enemy,
+    fighter::{FighterState, Side},
+    player::Player,
+};
+
+#[derive(Event, Clone, Copy, Debug)]
+pub struct AttackEvent {
+    pub attacker: Entity,
+    pub side: Side,
+    pub damage: u32,
+    /// Seconds the punch can connect for after it is thrown.
+    pub window: f32,
+}
+
+#[derive(Event, Clone, Copy, Debug)]
+pub struct HitEvent {
+    pub attacker: Entity,
+    pub target: Entity,
+    pub side: Side,
+    pub damage: u32,
+    /// Landed while the target was winding up or throwing a punch.
+    pub counter: bool,
+}
+
+#[derive(Event, Clone, Copy, Debug)]
+pub struct BlockedEvent {
+    pub attacker: Entity,
+    pub target: Entity,
+    pub side: Side,
+}
+
+#[derive(Event, Clone, Copy, Debug)]
+pub struct DodgedEvent {
+    pub attacker: Entity,
+    pub target: Entity,
+    pub side: Side,
+}
+
+/// A punch that ran out without connecting, being blocked or being dodged.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct WhiffEvent {
+    pub attacker: Entity,
+}
+
+/// How a fighter would take a punch arriving right now.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Defense {
+    Open,
+    Blocking,
+    Dodging,
+    /// Stunned or down; the punch passes through without resolving.
+    Untouchable,
+}
+
+struct ActiveAttack {
+    event: AttackEvent,
+    remaining: f32,
+    /// Set once the target has been seen dodging this punch.
+    dodged: bool,
+}
+
+/// Punches that have been thrown but not resolved yet.
+#[derive(Resource, Default)]
+pub struct ActiveAttacks(Vec<ActiveAttack>);
+
+impl ActiveAttacks {
+    /// Drops every punch in flight, e.g. when the bell rings.
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn resolve_attacks(
+    time: Res<Time>,
+    mut attacks: EventReader<AttackEvent>,
+    mut active: ResMut<ActiveAttacks>,
+    mut players: Query<(Entity, &mut Player)>,
+    mut enemies: Query<(Entity, &mut Enemy)>,
+    mut hits: EventWriter<HitEvent>,
+    mut blocks: EventWriter<BlockedEvent>,
+    mut dodges: EventWriter<DodgedEvent>,
+    mut whiffs: EventWriter<WhiffEvent>,
+) {
+    active.0.extend(attacks.read().map(|event| ActiveAttack {
+        event: *event,
+        remaining: event.window,
+        dodged: false,
+    }));
+
+    let mut resolved = Vec::new();
+    let mut stunned = Vec::new();
+    for (index, attack) in active.0.iter_mut().enumerate() {
+        let event = attack.event;
+        // the player punches enemies and enemies punch the player
+        let target = if players.contains(event.attacker) {
+            enemies.iter().next().map(|(entity, enemy)| (entity, enemy.defense()))
+        } else {
+            players.iter().next().map(|(entity, player)| (entity, player.defense()))
+        };
+        let Some((target, defense)) = target else {
+            whiffs.send(WhiffEvent { attacker: event.attacker });
+            resolved.push(index);
+            continue;
+        };
+
+        match defense {
+            Defense::Open => {
+                let mut target_state = None;
+                if let Ok((_, mut player)) = players.get_mut(target) {
+                    target_state = Some(player.fighter.state);
+                    player.take_hit(event.damage);
+                }
+                if let Ok((_, mut enemy)) = enemies.get_mut(target) {
+                    target_state = Some(enemy.fighter.state);
+                    enemy.take_hit(event.damage);
+                }
+                hits.send(HitEvent {
+                    attacker: event.attacker,
+                    target,
+                    side: event.side,
+                    damage: event.damage,
+                    counter: matches!(target_state, Some(FighterState::WindUp(_) | FighterState::Punching(_))),
+                });
+                stunned.push(target);
+                resolved.push(index);
+                continue;
+            }
+            Defense::Blocking => {
+                blocks.send(BlockedEvent {
+                    attacker: event.attacker,
+                    target,
+                    side: event.side,
+                });
+                resolved.push(index);
+                continue;
+            }
+            Defense::Dodging => attack.dodged = true,
+            Defense::Untouchable => {}
+        }
+
+        attack.remaining -= time.delta_seconds();
+        if attack.remaining <= 0. {
+            if attack.dodged {
+                dodges.send(DodgedEvent {
+                    attacker: event.attacker,
+                    target,
+                    side: event.side,
+                });
+            } else {
+                whiffs.send(WhiffEvent { attacker: event.attacker });
+            }
+            resolved.push(index);
+        }
+    }
+
+    // a fighter that gets hit loses whatever punch it had out
+    let mut index = 0;
+    active.0.retain(|attack| {
+        let keep = !resolved.contains(&index) && !stunned.contains(&attack.event.attacker);
+        index += 1;
+        keep
+    });
+}
@@ -0,0 +1,218 @@
+//! Sound effects, announcer lines, fighter voices and music. Everything here
+//! is played in answer to combat events or state changes, never by the
+//! systems that simulate the fight. Sounds aren't waited on while loading;
+//! one that is missing is reported by the asset server and stays silent.
+
+use bevy::{audio::Volume, prelude::*};
+
+use crate::{
+    attack::{AttackEvent, BlockedEvent, DodgedEvent, HitEvent, WhiffEvent},
+    fighter_def::{FighterDef, FighterSource},
+    knockdown::{CountEvent, KnockdownEvent},
+    loading::GameAssets,
+    roster::{Progress, Roster},
+    round::{BellEvent, Finish, Round},
+    save::Settings,
+    state::FightEntity,
+};
+
+/// Played when a circuit doesn't name its own music.
+const DEFAULT_MUSIC: &str = "music/fight.wav";
+
+#[derive(Resource)]
+pub struct Sounds {
+    pub whiff: Handle<AudioSource>,
+    pub hit: Handle<AudioSource>,
+    pub block: Handle<AudioSource>,
+    pub dodge: Handle<AudioSource>,
+    pub knockdown: Handle<AudioSource>,
+    pub bell: Handle<AudioSource>,
+    pub fight: Handle<AudioSource>,
+    /// The referee's count, from one to ten.
+    pub counts: Vec<Handle<AudioSource>>,
+    pub ko: Handle<AudioSource>,
+    pub tko: Handle<AudioSource>,
+    pub decision: Handle<AudioSource>,
+}
+
+/// A fighter's voice clips, loaded from its definition.
+#[derive(Component)]
+pub struct Voice {
+    pub punch: Option<Handle<AudioSource>>,
+    pub hit: Option<Handle<AudioSource>>,
+    pub down: Option<Handle<AudioSource>>,
+}
+
+#[derive(Component)]
+pub struct Music;
+
+pub fn load_sounds(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(Sounds {
+        whiff: asset_server.load("sounds/whiff.wav"),
+        hit: asset_server.load("sounds/hit.wav"),
+        block: asset_server.load("sounds/block.wav"),
+        dodge: asset_server.load("sounds/dodge.wav"),
+        knockdown: asset_server.load("sounds/knockdown.wav"),
+        bell: asset_server.load("sounds/bell.wav"),
+        fight: asset_server.load("sounds/announcer/fight.wav"),
+        counts: (1..=10)
+            .map(|count| asset_server.load(format!("sounds/announcer/count_{count}.wav")))
+            .collect(),
+        ko: asset_server.load("sounds/announcer/ko.wav"),
+        tko: asset_server.load("sounds/announcer/tko.wav"),
+        decision: asset_server.load("sounds/announcer/decision.wav"),
+    });
+}
+
+fn play(commands: &mut Commands, sound: &Handle<AudioSource>, level: f32) {
+    commands.spawn(AudioBundle {
+        source: sound.clone(),
+        settings: PlaybackSettings::DESPAWN.with_volume(Volume::new_relative(level)),
+    });
+}
+
+/// Gives every fighter its voice once its definition is in.
+pub fn load_voices(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    defs: Res<Assets<FighterDef>>,
+    sources: Query<(Entity, &FighterSource), Without<Voice>>,
+) {
+    for (entity, source) in &sources {
+        let Some(def) = defs.get(&source.def) else {
+            continue;
+        };
+        let load = |path: &Option<String>| path.as_ref().map(|path| asset_server.load(path.clone()));
+        commands.entity(entity).insert(Voice {
+            punch: load(&def.voice.punch),
+            hit: load(&def.voice.hit),
+            down: load(&def.voice.down),
+        });
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn play_combat_sounds(
+    mut commands: Commands,
+    sounds: Res<Sounds>,
+    settings: Res<Settings>,
+    voices: Query<&Voice>,
+    mut attacks: EventReader<AttackEvent>,
+    mut hits: EventReader<HitEvent>,
+    mut blocks: EventReader<BlockedEvent>,
+    mut dodges: EventReader<DodgedEvent>,
+    mut whiffs: EventReader<WhiffEvent>,
+    mut knockdowns: EventReader<KnockdownEvent>,
+    mut counts: EventReader<CountEvent>,
+    mut bells: EventReader<BellEvent>,
+) {
+    let level = settings.sfx_level();
+    let voice = |commands: &mut Commands, fighter: Entity, clip: fn(&Voice) -> &Option<Handle<AudioSource>>| {
+        if let Some(sound) = voices.get(fighter).ok().and_then(|voice| clip(voice).as_ref()) {
+            play(commands, sound, level);
+        }
+    };
+
+    for attack in attacks.read() {
+        voice(&mut commands, attack.attacker, |voice| &voice.punch);
+    }
+    for hit in hits.read() {
+        play(&mut commands, &sounds.hit, level);
+        voice(&mut commands, hit.target, |voice| &voice.hit);
+    }
+    for _ in blocks.read() {
+        play(&mut commands, &sounds.block, level);
+    }
+    for _ in dodges.read() {
+        play(&mut commands, &sounds.dodge, level);
+    }
+    for _ in whiffs.read() {
+        play(&mut commands, &sounds.whiff, level);
+    }
+    for knockdown in knockdowns.read() {
+        play(&mut commands, &sounds.knockdown, level);
+        voice(&mut commands, knockdown.fighter, |voice| &voice.down);
+    }
+    for CountEvent(count) in counts.read() {
+        if let Some(sound) = sounds.counts.get(*count as usize - 1) {
+            play(&mut commands, sound, level);
+        }
+    }
+    for _ in bells.read() {
+        play(&mut commands, &sounds.bell, level);
+    }
+}
+
+/// The opening bell and the call to fight, as the pre-fight screen closes.
+pub fn announce_fight(mut commands: Commands, sounds: Res<Sounds>, settings: Res<Settings>) {
+    play(&mut commands, &sounds.bell, settings.sfx_level());
+    play(&mut commands, &sounds.fight, settings.sfx_level());
+}
+
+/// Calls the result once, when the fight ends.
+pub fn announce_result(
+    mut commands: Commands,
+    sounds: Res<Sounds>,
+    settings: Res<Settings>,
+    round: Res<Round>,
+    mut announced: Local<bool>,
+) {
+    let Some(result) = round.result else {
+        *announced = false;
+        return;
+    };
+    if *announced {
+        return;
+    }
+    *announced = true;
+    let sound = match result.finish {
+        Finish::KO => &sounds.ko,
+        Finish::TKO => &sounds.tko,
+        Finish::Decision => &sounds.decision,
+    };
+    play(&mut commands, sound, settings.sfx_level());
+}
+
+/// Loops the music of the challenger's circuit for as long as the fight lasts.
+pub fn start_music(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    assets: Res<GameAssets>,
+    rosters: Res<Assets<Roster>>,
+    progress: Res<Progress>,
+    settings: Res<Settings>,
+) {
+    let music = rosters
+        .get(&assets.roster)
+        .and_then(|roster| roster.circuit_of(roster.challenger(&progress)))
+        .and_then(|circuit| circuit.music.clone())
+        .unwrap_or_else(|| DEFAULT_MUSIC.to_string());
+    commands.spawn((
+        AudioBundle {
+            source: asset_server.load(music),
+            settings: PlaybackSettings::LOOP.with_volume(Volume::new_relative(settings.music_level())),
+        },
+        Music,
+        FightEntity,
+    ));
+}
+
+/// Keeps music that is already playing in step with the volume settings.
+pub fn update_music_volume(settings: Res<Settings>, sinks: Query<&AudioSink, With<Music>>) {
+    for sink in &sinks {
+        sink.set_volume(settings.music_level());
+    }
+}
+
+/// Holds the music while the fight is paused.
+pub fn pause_music(sinks: Query<&AudioSink, With<Music>>) {
+    for sink in &sinks {
+        sink.pause();
+    }
+}
+
+pub fn resume_music(sinks: Query<&AudioSink, With<Music>>) {
+    for sink in &sinks {
+        sink.play();
+    }
+}
@@ -0,0 +1,113 @@
+use bevy::{input::InputSystem, prelude::*};
+
+use crate::{
+    attack::{self, ActiveAttacks, AttackEvent, BlockedEvent, DodgedEvent, HitEvent, WhiffEvent},
+    controls,
+    enemy::{self, Enemy},
+    impact::{self, HitStop},
+    knockdown::{self, Count, CountEvent, KnockdownEvent},
+    pause,
+    player::{self, Player},
+    replay::{self, Playback, Recorder},
+    round::{self, BellEvent, Round},
+    score::{self, Score},
+    state::{self, AppState},
+    timestep,
+    training::{self, FrameData, Training},
+};
+
+/// Everything that simulates a fight, with no rendering, windowing or asset
+/// loading, so it also runs under `MinimalPlugins`. Expects a
+/// [`CombatRng`](crate::rng::CombatRng) and an `Input<KeyCode>` to be provided.
+/// Insert a [`Round`] beforehand to change the number or length of rounds,
+/// [`Bindings`](controls::Bindings) to change the controls and a [`HitStop`]
+/// to turn hit-stop on. A [`Training`] resource makes it a training fight.
+/// Entering [`AppState::PreFight`] starts a new fight, set up from the
+/// [`Playback`] when there is one.
+/// Combat only advances in [`AppState::Fighting`], and the virtual clock
+/// stops in [`AppState::Paused`].
+pub struct CombatPlugin;
+
+impl Plugin for CombatPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_state::<AppState>()
+            .insert_resource(Time::<Fixed>::from_hz(timestep::TICK_HZ))
+            .init_resource::<controls::Bindings>()
+            .init_resource::<controls::ActionState>()
+            .init_resource::<controls::PlayerInput>()
+            .init_resource::<ActiveAttacks>()
+            .init_resource::<Round>()
+            .init_resource::<HitStop>()
+            .init_resource::<Score>()
+            .add_event::<BellEvent>()
+            .add_event::<KnockdownEvent>()
+            .add_event::<CountEvent>()
+            .add_event::<AttackEvent>()
+            .add_event::<HitEvent>()
+            .add_event::<BlockedEvent>()
+            .add_event::<DodgedEvent>()
+            .add_event::<WhiffEvent>()
+            .add_systems(PreUpdate, (controls::update_actions, controls::buffer_player_input).chain().after(InputSystem))
+            .add_systems(FixedUpdate, (
+                timestep::begin_tick,
+                impact::count_down_hit_stop,
+                (
+                    replay::play_back_input
+                        .run_if(resource_exists::<Playback>())
+                        .run_if(fighters_ready),
+                    training::apply_training_action.run_if(resource_exists::<Training>()),
+                    (
+                        player::update_player_movement,
+                        enemy::update_enemy_movement,
+                        attack::resolve_attacks,
+                        player::update_hearts,
+                        training::refill_health.run_if(resource_exists::<Training>()),
+                        knockdown::start_knockdown,
+                        score::update_score,
+                        training::update_frame_data.run_if(resource_exists::<FrameData>()),
+                    ).chain().run_if(round::round_in_progress),
+                    knockdown::update_count.run_if(resource_exists::<Count>()),
+                    // the clock stands still in training
+                    round::update_round
+                        .run_if(fighters_ready)
+                        .run_if(not(resource_exists::<Training>())),
+                    replay::record_input
+                        .run_if(resource_exists::<Recorder>())
+                        .run_if(fighters_ready),
+                    controls::consume_player_input,
+                    impact::start_hit_stop,
+                ).chain().run_if(impact::not_hit_stopped),
+            ).chain().run_if(in_state(AppState::Fighting)))
+            .add_systems(OnEnter(AppState::PreFight), (
+                replay::set_up_replayed_fight.run_if(resource_exists::<Playback>()),
+                replay::record_fight_setup.run_if(resource_exists::<Recorder>()),
+                reset_fight,
+            ).chain())
+            .add_systems(Update, replay::record_training_action.run_if(resource_exists::<Recorder>().and_then(resource_exists::<Training>())))
+            .add_systems(OnEnter(AppState::Paused), pause::freeze_time)
+            .add_systems(OnExit(AppState::Paused), (pause::unfreeze_time, state::clear_player_input))
+            .add_systems(Update, timestep::interpolate_translation)
+            .add_systems(Last, replay::save_recording.run_if(resource_exists::<Recorder>()));
+    }
+}
+
+/// Every fight starts from round one with nothing in flight and no score.
+fn reset_fight(
+    mut commands: Commands,
+    mut round: ResMut<Round>,
+    mut attacks: ResMut<ActiveAttacks>,
+    mut stop: ResMut<HitStop>,
+    mut score: ResMut<Score>,
+) {
+    round.restart();
+    attacks.clear();
+    stop.clear();
+    *score = Score::default();
+    commands.remove_resource::<Count>();
+}
+
+/// Fighters appear once their definitions load, which can take a different
+/// number of ticks from run to run; replays only count ticks after that.
+fn fighters_ready(player: Query<(), With<Player>>, enemy: Query<(), With<Enemy>>) -> bool {
+    !player.is_empty() && !enemy.is_empty()
+}
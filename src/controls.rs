@@ -0,0 +1,275 @@
+//! Player input. Keys and gamepad inputs are bound to [`Action`]s in
+//! `bindings.ron`, next to the save, and everything else reads actions rather
+//! than raw input. The file is written out with the defaults on first run;
+//! actions it leaves out keep their default bindings.
+//!
+//! ```ron
+//! {
+//!     Block: [Key(Down), Button(DPadDown), Axis(LeftStickY, -1.0)],
+//!     PunchLeft: [Key(Z), Key(A), Button(West)],
+//! }
+//! ```
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt, fs,
+    path::Path,
+};
+
+use bevy::prelude::*;
+use ron::error::SpannedError;
+use serde::{Deserialize, Serialize};
+
+use crate::save;
+
+/// Something the player can do, whatever it is bound to.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Action {
+    Block,
+    DodgeLeft,
+    DodgeRight,
+    PunchLeft,
+    PunchRight,
+    /// Pauses or resumes a fight.
+    Pause,
+    /// Moves on from a menu screen.
+    Confirm,
+    /// Moves through the items of a menu.
+    MenuUp,
+    MenuDown,
+    /// Turns a menu setting down or up.
+    MenuLeft,
+    MenuRight,
+    /// Leaves a menu screen without confirming.
+    Back,
+    /// Takes back the last digit of a password.
+    Erase,
+    /// Opens the password screen from the title.
+    Password,
+    /// Starts training mode from the title.
+    Training,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum Binding {
+    Key(KeyCode),
+    /// A button on any connected gamepad.
+    Button(GamepadButtonType),
+    /// A stick pushed past [`AXIS_THRESHOLD`] toward the sign of the value.
+    Axis(GamepadAxisType, f32),
+}
+
+pub const AXIS_THRESHOLD: f32 = 0.5;
+
+#[derive(Resource, Clone, Debug)]
+pub struct Bindings(pub BTreeMap<Action, Vec<Binding>>);
+
+impl Default for Bindings {
+    fn default() -> Self {
+        use Binding::*;
+        Bindings(BTreeMap::from([
+            (Action::Block, vec![Key(KeyCode::Down), Button(GamepadButtonType::DPadDown), Axis(GamepadAxisType::LeftStickY, -1.0)]),
+            (Action::DodgeLeft, vec![Key(KeyCode::Left), Button(GamepadButtonType::DPadLeft), Axis(GamepadAxisType::LeftStickX, -1.0)]),
+            (Action::DodgeRight, vec![Key(KeyCode::Right), Button(GamepadButtonType::DPadRight), Axis(GamepadAxisType::LeftStickX, 1.0)]),
+            (Action::PunchLeft, vec![Key(KeyCode::Z), Button(GamepadButtonType::West)]),
+            (Action::PunchRight, vec![Key(KeyCode::X), Button(GamepadButtonType::South)]),
+            (Action::Pause, vec![Key(KeyCode::Escape), Button(GamepadButtonType::Start)]),
+            (Action::Confirm, vec![Key(KeyCode::Return), Button(GamepadButtonType::Start), Button(GamepadButtonType::South)]),
+            (Action::MenuUp, vec![Key(KeyCode::Up), Button(GamepadButtonType::DPadUp), Axis(GamepadAxisType::LeftStickY, 1.0)]),
+            (Action::MenuDown, vec![Key(KeyCode::Down), Button(GamepadButtonType::DPadDown), Axis(GamepadAxisType::LeftStickY, -1.0)]),
+            (Action::MenuLeft, vec![Key(KeyCode::Left), Button(GamepadButtonType::DPadLeft), Axis(GamepadAxisType::LeftStickX, -1.0)]),
+            (Action::MenuRight, vec![Key(KeyCode::Right), Button(GamepadButtonType::DPadRight), Axis(GamepadAxisType::LeftStickX, 1.0)]),
+            (Action::Back, vec![Key(KeyCode::Escape), Button(GamepadButtonType::East)]),
+            (Action::Erase, vec![Key(KeyCode::Back), Button(GamepadButtonType::West)]),
+            (Action::Password, vec![Key(KeyCode::P), Button(GamepadButtonType::Select)]),
+            (Action::Training, vec![Key(KeyCode::T), Button(GamepadButtonType::North)]),
+        ]))
+    }
+}
+
+#[derive(Debug)]
+pub enum BindingsError {
+    Io(std::io::Error),
+    Parse(SpannedError),
+    Write(ron::Error),
+}
+
+impl fmt::Display for BindingsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BindingsError::Io(err) => write!(f, "could not access bindings file: {err}"),
+            BindingsError::Parse(err) => write!(
+                f,
+                "could not parse bindings file at {}:{}: {}",
+                err.position.line, err.position.col, err.code
+            ),
+            BindingsError::Write(err) => write!(f, "could not write bindings: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for BindingsError {}
+
+impl From<std::io::Error> for BindingsError {
+    fn from(err: std::io::Error) -> Self {
+        BindingsError::Io(err)
+    }
+}
+
+impl From<SpannedError> for BindingsError {
+    fn from(err: SpannedError) -> Self {
+        BindingsError::Parse(err)
+    }
+}
+
+impl From<ron::Error> for BindingsError {
+    fn from(err: ron::Error) -> Self {
+        BindingsError::Write(err)
+    }
+}
+
+impl Bindings {
+    pub fn to_ron(&self) -> Result<String, BindingsError> {
+        Ok(ron::ser::to_string_pretty(&self.0, ron::ser::PrettyConfig::default())?)
+    }
+
+    /// Actions missing from `text` keep their default bindings.
+    pub fn from_ron(text: &str) -> Result<Bindings, BindingsError> {
+        let file: BTreeMap<Action, Vec<Binding>> = ron::from_str(text)?;
+        let mut bindings = Bindings::default();
+        bindings.0.extend(file);
+        Ok(bindings)
+    }
+}
+
+/// Reads the bindings at `path`, writing out the defaults if there are none
+/// yet so there is a file to edit. Falls back to the defaults on any error.
+pub fn load_bindings(path: &Path) -> Bindings {
+    let read = match fs::read_to_string(path) {
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            let bindings = Bindings::default();
+            let written = bindings.to_ron().and_then(|text| {
+                if let Some(dir) = path.parent() {
+                    fs::create_dir_all(dir)?;
+                }
+                Ok(fs::write(path, text)?)
+            });
+            if let Err(err) = written {
+                warn!("{}: {err}", path.display());
+            }
+            return bindings;
+        }
+        read => read.map_err(BindingsError::from),
+    };
+    match read.and_then(|text| Bindings::from_ron(&text)) {
+        Ok(bindings) => bindings,
+        Err(err) => {
+            error!("{}: {err}; using the default bindings", path.display());
+            Bindings::default()
+        }
+    }
+}
+
+pub fn load_bindings_file(mut commands: Commands) {
+    if let Some(path) = save::data_file("bindings.ron") {
+        commands.insert_resource(load_bindings(&path));
+    }
+}
+
+/// Actions held this frame, through any of their bindings.
+#[derive(Resource, Default, Debug)]
+pub struct ActionState {
+    pressed: HashSet<Action>,
+    just_pressed: HashSet<Action>,
+}
+
+impl ActionState {
+    pub fn pressed(&self, action: Action) -> bool {
+        self.pressed.contains(&action)
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.just_pressed.contains(&action)
+    }
+}
+
+/// Gamepad input is optional so this also runs under `MinimalPlugins`.
+pub fn update_actions(
+    bindings: Res<Bindings>,
+    keys: Res<Input<KeyCode>>,
+    gamepads: Option<Res<Gamepads>>,
+    buttons: Option<Res<Input<GamepadButton>>>,
+    axes: Option<Res<Axis<GamepadAxis>>>,
+    mut actions: ResMut<ActionState>,
+) {
+    let gamepads: Vec<Gamepad> = gamepads.map(|gamepads| gamepads.iter().collect()).unwrap_or_default();
+    let is_down = |binding: &Binding| match *binding {
+        Binding::Key(key) => keys.pressed(key),
+        Binding::Button(button) => buttons.as_ref().is_some_and(|buttons| {
+            gamepads.iter().any(|gamepad| buttons.pressed(GamepadButton::new(*gamepad, button)))
+        }),
+        Binding::Axis(axis, direction) => axes.as_ref().is_some_and(|axes| {
+            gamepads.iter().any(|gamepad| {
+                axes.get(GamepadAxis::new(*gamepad, axis))
+                    .is_some_and(|value| value * direction.signum() > AXIS_THRESHOLD)
+            })
+        }),
+    };
+
+    let pressed: HashSet<Action> = bindings
+        .0
+        .iter()
+        .filter(|(_, bound)| bound.iter().any(is_down))
+        .map(|(action, _)| *action)
+        .collect();
+    actions.just_pressed = pressed.difference(&actions.pressed).copied().collect();
+    actions.pressed = pressed;
+}
+
+/// What the player asked for since the last combat tick. Presses are gathered
+/// every frame and held until a fixed tick consumes them, so none are lost or
+/// repeated when frames and ticks don't line up.
+#[derive(Resource, Default, Clone, Copy, PartialEq, Debug)]
+pub struct PlayerInput {
+    pub block: bool,
+    pub dodge_left: bool,
+    pub dodge_right: bool,
+    pub punch_left: bool,
+    pub punch_right: bool,
+}
+
+impl PlayerInput {
+    pub fn to_bits(self) -> u8 {
+        self.block as u8
+            | (self.dodge_left as u8) << 1
+            | (self.dodge_right as u8) << 2
+            | (self.punch_left as u8) << 3
+            | (self.punch_right as u8) << 4
+    }
+
+    pub fn from_bits(bits: u8) -> PlayerInput {
+        PlayerInput {
+            block: bits & 1 != 0,
+            dodge_left: bits & 1 << 1 != 0,
+            dodge_right: bits & 1 << 2 != 0,
+            punch_left: bits & 1 << 3 != 0,
+            punch_right: bits & 1 << 4 != 0,
+        }
+    }
+}
+
+pub fn buffer_player_input(actions: Res<ActionState>, mut input: ResMut<PlayerInput>) {
+    input.block = actions.pressed(Action::Block);
+    input.dodge_left |= actions.just_pressed(Action::DodgeLeft);
+    input.dodge_right |= actions.just_pressed(Action::DodgeRight);
+    input.punch_left |= actions.just_pressed(Action::PunchLeft);
+    input.punch_right |= actions.just_pressed(Action::PunchRight);
+}
+
+/// Runs at the end of every tick; `block` is a held state and is left alone.
+pub fn consume_player_input(mut input: ResMut<PlayerInput>) {
+    input.dodge_left = false;
+    input.dodge_right = false;
+    input.punch_left = false;
+    input.punch_right = false;
+}
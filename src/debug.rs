@@ -0,0 +1,100 @@
+//! A developer overlay, built with `--features debug_overlay` and toggled
+//! with F3. It shows what each fighter is doing and how long it has left,
+//! how many punches it has taken, where its animator is and the frame rate,
+//! refreshed every frame. Fighters share one state timer, so that stands in
+//! for the dodge, punch, hit, block and wait timers.
+
+use bevy::{
+    diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin},
+    prelude::*,
+    sprite::Anchor,
+};
+
+use crate::{
+    enemy::Enemy,
+    fighter::{Fighter, StateClips},
+    player::Player,
+};
+
+const TOGGLE_KEY: KeyCode = KeyCode::F3;
+
+pub struct DebugOverlayPlugin;
+
+impl Plugin for DebugOverlayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(FrameTimeDiagnosticsPlugin)
+            .add_systems(Startup, spawn_debug_overlay)
+            .add_systems(Update, (toggle_debug_overlay, update_debug_overlay).chain());
+    }
+}
+
+#[derive(Component)]
+struct DebugText;
+
+fn spawn_debug_overlay(mut commands: Commands) {
+    // the built-in font, so the overlay works even when assets don't
+    commands.spawn((
+        Text2dBundle {
+            text: Text::from_section("", TextStyle { font_size: 14.0, color: Color::YELLOW, ..default() }),
+            text_anchor: Anchor::TopLeft,
+            transform: Transform::from_translation(Vec3::new(-250.0, 215.0, 50.0)),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        DebugText,
+    ));
+}
+
+fn toggle_debug_overlay(keys: Res<Input<KeyCode>>, mut overlay: Query<&mut Visibility, With<DebugText>>) {
+    if !keys.just_pressed(TOGGLE_KEY) {
+        return;
+    }
+    for mut visibility in &mut overlay {
+        *visibility = match *visibility {
+            Visibility::Hidden => Visibility::Visible,
+            _ => Visibility::Hidden,
+        };
+    }
+}
+
+fn describe<S: StateClips>(name: &str, fighter: &Fighter<S>) -> String {
+    let animator = &fighter.animator;
+    format!(
+        "{name} {:?} {:.2}s\n  HP {}/{} HITS {}\n  CLIP {}-{} @{} {:.2}s {}",
+        fighter.state,
+        fighter.timer,
+        fighter.health,
+        fighter.max_health,
+        fighter.hits_taken_total,
+        animator.indices.first,
+        animator.indices.last,
+        animator.index,
+        animator.timer.duration().as_secs_f32(),
+        if animator.loops { "LOOP" } else { "ONCE" },
+    )
+}
+
+fn update_debug_overlay(
+    diagnostics: Res<DiagnosticsStore>,
+    players: Query<&Player>,
+    enemies: Query<&Enemy>,
+    mut overlay: Query<(&mut Text, &Visibility), With<DebugText>>,
+) {
+    let fps = diagnostics
+        .get(FrameTimeDiagnosticsPlugin::FPS)
+        .and_then(|fps| fps.smoothed())
+        .unwrap_or_default();
+    let mut lines = vec![format!("FPS {fps:.0}")];
+    lines.extend(players.iter().map(|player| {
+        format!("{}\n  HEARTS {}", describe("PLAYER", &player.fighter), player.hearts)
+    }));
+    lines.extend(enemies.iter().map(|enemy| {
+        format!("{}\n  COMBO {}", describe("ENEMY", &enemy.fighter), enemy.hits_taken())
+    }));
+    let value = lines.join("\n");
+    for (mut text, visibility) in &mut overlay {
+        if *visibility != Visibility::Hidden {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
@@ -0,0 +1,160 @@
+//! Short-lived sprites at the point of contact: sparks on hits, a flash on
+//! blocks, sweat on dodges and stars over a fighter who goes down. Each one
+//! plays its clip once through [`animate_sprite`](crate::animate_sprite) and is
+//! gone when the clip ends. Like sounds, the sheet isn't waited on while
+//! loading.
+
+use bevy::prelude::*;
+
+use crate::{
+    attack::{BlockedEvent, DodgedEvent, HitEvent},
+    knockdown::KnockdownEvent,
+    player::Player,
+    state::FightEntity,
+    util::*,
+};
+
+const FRAME_SIZE: f32 = 16.0;
+const FRAMES: usize = 14;
+/// Above both fighters.
+const EFFECT_Z: f32 = 5.0;
+/// Where punches land, above a fighter's origin.
+const CONTACT_HEIGHT: f32 = 40.0;
+/// How far to the side of the middle a punch lands.
+const CONTACT_SPREAD: f32 = 14.0;
+const STARS_HEIGHT: f32 = 64.0;
+
+#[derive(Resource)]
+pub struct Effects {
+    pub atlas: Handle<TextureAtlas>,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Effect {
+    Spark,
+    Flash,
+    Sweat,
+    Stars,
+}
+
+impl Effect {
+    fn clip(self) -> SpriteClip {
+        let (first, last, frame_time) = match self {
+            Effect::Spark => (0, 3, 0.04),
+            Effect::Flash => (4, 6, 0.05),
+            Effect::Sweat => (7, 9, 0.08),
+            Effect::Stars => (10, 13, 0.12),
+        };
+        SpriteClip {
+            indices: AnimationIndices { first, last },
+            frame_time,
+            loops: false,
+        }
+    }
+}
+
+/// Despawned by `animate_sprite` once its last frame has been shown.
+#[derive(Component)]
+pub struct OneShot;
+
+pub fn load_effects(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+) {
+    let atlas = TextureAtlas::from_grid(
+        asset_server.load("sprites/atlases/effects.png"),
+        Vec2::splat(FRAME_SIZE),
+        FRAMES,
+        1,
+        None,
+        None,
+    );
+    commands.insert_resource(Effects {
+        atlas: texture_atlases.add(atlas),
+    });
+}
+
+fn spawn_effect(commands: &mut Commands, effects: &Effects, effect: Effect, at: Vec3) {
+    let clip = effect.clip();
+    commands.spawn((
+        SpriteSheetBundle {
+            texture_atlas: effects.atlas.clone(),
+            sprite: TextureAtlasSprite::new(clip.indices.first),
+            transform: Transform::from_translation(at.truncate().extend(EFFECT_Z)).with_scale(Vec3::splat(2.0)),
+            ..default()
+        },
+        clip.indices,
+        AnimationTimer(Timer::from_seconds(clip.frame_time, TimerMode::Repeating)),
+        OneShot,
+        FightEntity,
+    ));
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn spawn_combat_effects(
+    mut commands: Commands,
+    effects: Res<Effects>,
+    transforms: Query<&Transform>,
+    players: Query<(), With<Player>>,
+    mut hits: EventReader<HitEvent>,
+    mut blocks: EventReader<BlockedEvent>,
+    mut dodges: EventReader<DodgedEvent>,
+    mut knockdowns: EventReader<KnockdownEvent>,
+) {
+    // the player is seen from behind, so their punches land on the side they
+    // throw from and the enemy's land mirrored
+    let contact = |attacker: Entity, target: Entity, side_sign: f32| {
+        let mirror = if players.contains(attacker) { 1. } else { -1. };
+        transforms.get(target).ok().map(|transform| {
+            transform.translation + Vec3::new(side_sign * mirror * CONTACT_SPREAD, CONTACT_HEIGHT, 0.)
+        })
+    };
+
+    let hits = hits.read().map(|hit| (Effect::Spark, contact(hit.attacker, hit.target, hit.side.sign())));
+    let blocks = blocks.read().map(|block| (Effect::Flash, contact(block.attacker, block.target, block.side.sign())));
+    let dodges = dodges.read().map(|dodge| (Effect::Sweat, contact(dodge.attacker, dodge.target, dodge.side.sign())));
+    let knockdowns = knockdowns.read().map(|knockdown| {
+        let at = transforms.get(knockdown.fighter).ok().map(|transform| transform.translation + Vec3::Y * STARS_HEIGHT);
+        (Effect::Stars, at)
+    });
+    for (effect, at) in hits.chain(blocks).chain(dodges).chain(knockdowns) {
+        if let Some(at) = at {
+            spawn_effect(&mut commands, &effects, effect, at);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::time::TimeUpdateStrategy;
+
+    use super::*;
+
+    const FRAME_TIME: f32 = 0.1;
+
+    #[test]
+    fn one_shots_despawn_at_the_end_of_their_clip_and_others_wrap() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(FRAME_TIME)))
+            .add_systems(Update, crate::animate_sprite);
+        let indices = AnimationIndices { first: 0, last: 1 };
+        let timer = || AnimationTimer(Timer::from_seconds(FRAME_TIME, TimerMode::Repeating));
+        let one_shot = app.world.spawn((indices, timer(), TextureAtlasSprite::new(0), OneShot)).id();
+        let looping = app.world.spawn((indices, timer(), TextureAtlasSprite::new(0))).id();
+        let index = |app: &App, entity| app.world.get::<TextureAtlasSprite>(entity).map(|sprite| sprite.index);
+
+        // the first update only starts the clock
+        app.update();
+        app.update();
+        assert_eq!(index(&app, one_shot), Some(1));
+        assert_eq!(index(&app, looping), Some(1));
+
+        app.update();
+        assert!(app.world.get_entity(one_shot).is_none());
+        assert_eq!(index(&app, looping), Some(0));
+    }
+}
@@ -1,209 +1,207 @@
 use bevy::{
     ecs::{
         component::Component,
-        system::{Query, Res},
+        entity::Entity,
+        event::EventWriter,
+        system::{Query, Res, ResMut},
     },
-    input::{keyboard::KeyCode, Input},
-    math::{vec2, Vec3},
-    prelude::default,
+    math::Vec3,
     time::Time,
-    transform::components::Transform, sprite::{SpriteSheetBundle, TextureAtlas, TextureAtlasSprite},
+    sprite::TextureAtlasSprite,
 };
+use serde::Deserialize;
 
-use crate::{util::*, AnimationIndices, AnimationTimer, player::{Player, self}};
-
-use rand::*;
-use rand::prelude::*;
-
-#[derive(Default)]
-enum ActionDirection {
-    #[default]
-    None,
-    Left,
-    Right,
-}
+use crate::{util::*, attack::{AttackEvent, Defense}, fighter::{Fighter, FighterState, Side, StateClips}, fighter_def::{FighterClips, FighterDefError}, pattern::{AttackPattern, EnemyAction}, rng::CombatRng, timestep::SimTranslation};
 
 #[derive(Component)]
 pub struct Enemy {
-    action_dir: ActionDirection,
-    pub punch_timer: f32,
-    states: EnemyStates,
-    block_timer: f32,
-    wait_timer: f32,
-    origin: Vec3,
-    animator: Animator,
-    is_hit_timer: f32,
+    pub fighter: Fighter<EnemyStates>,
+    /// Side of the last punch or feint, reused for counter-punches.
+    side: Side,
+    feint: bool,
     hits_taken: i32,
-    hits_taken_total: i32,
+    pattern: AttackPattern,
+    pub stats: EnemyStats,
+}
+
+/// What sets one opponent apart from another besides looks and pattern,
+/// read from the `stats` of a fighter definition.
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(default)]
+pub struct EnemyStats {
+    pub max_health: u32,
+    pub punch_damage: u32,
+    /// Chance of beating the count after the first knockdown in a round;
+    /// each further knockdown multiplies it in again.
+    pub get_up_chance: f32,
+}
+
+impl Default for EnemyStats {
+    fn default() -> Self {
+        EnemyStats {
+            max_health: 96,
+            punch_damage: 16,
+            get_up_chance: 0.5,
+        }
+    }
 }
 
 pub struct EnemyStates {
-    pub idle: AnimationIndices,
-    pub punch_warning: AnimationIndices,
-    pub punch: AnimationIndices,
-    pub hit: AnimationIndices,
-    pub block: AnimationIndices,
-    pub death: AnimationIndices
+    pub idle: SpriteClip,
+    pub punch_warning: SpriteClip,
+    pub punch: SpriteClip,
+    pub hit: SpriteClip,
+    pub block: SpriteClip,
+    pub death: SpriteClip
+}
+
+impl EnemyStates {
+    pub fn from_clips(clips: &FighterClips) -> Result<EnemyStates, FighterDefError> {
+        Ok(EnemyStates {
+            idle: clips.get("idle")?,
+            punch_warning: clips.get("punch_warning")?,
+            punch: clips.get("punch")?,
+            hit: clips.get("hit")?,
+            block: clips.get("block")?,
+            death: clips.get("death")?,
+        })
+    }
+}
+
+impl StateClips for EnemyStates {
+    fn clip(&self, state: FighterState) -> SpriteClip {
+        match state {
+            // enemies don't dodge
+            FighterState::Idle | FighterState::Dodging(_) => self.idle,
+            FighterState::Blocking => self.block,
+            FighterState::WindUp(_) => self.punch_warning,
+            FighterState::Punching(_) => self.punch,
+            FighterState::Hit => self.hit,
+            FighterState::Down | FighterState::KO => self.death,
+        }
+    }
 }
 
 impl Enemy {
-    pub fn new(origin: Vec3, animator: Animator, states: EnemyStates) -> Enemy {
+    pub fn new(origin: Vec3, states: EnemyStates, pattern: AttackPattern, stats: EnemyStats) -> Enemy {
+        let mut fighter = Fighter::new(origin, states, stats.max_health);
+        // opens with a jab
+        fighter.enter(FighterState::Punching(Side::Left), PUNCH_DURATION);
         Enemy {
-            states: states,
-            action_dir: ActionDirection::None,
-            punch_timer: PUNCH_DURATION,
-            block_timer: 0.0,
-            is_hit_timer: 0.0,
-            wait_timer: 0.0,
-            origin: origin,
-            animator: animator,
+            fighter,
+            side: Side::Left,
+            feint: false,
             hits_taken: 0,
-            hits_taken_total: 0,
+            pattern,
+            stats,
+        }
+    }
+
+    pub fn set_pattern(&mut self, pattern: AttackPattern) {
+        self.pattern = pattern;
+    }
+
+    /// Swaps in new stats, keeping the share of health left.
+    pub fn set_stats(&mut self, stats: EnemyStats) {
+        let fraction = self.fighter.health_fraction();
+        self.fighter.max_health = stats.max_health;
+        self.fighter.health = (stats.max_health as f32 * fraction).round() as u32;
+        self.stats = stats;
+    }
+
+    /// Shrugs off punches after two in a row until it gets to act again.
+    pub fn defense(&self) -> Defense {
+        if self.hits_taken >= 2 {
+            return Defense::Untouchable;
+        }
+        self.fighter.defense()
+    }
+
+    /// Punches taken since it last got to act.
+    #[cfg(feature = "debug_overlay")]
+    pub fn hits_taken(&self) -> i32 {
+        self.hits_taken
+    }
+
+    pub fn take_hit(&mut self, damage: u32) {
+        self.fighter.take_hit(IS_HIT_TIMER, damage);
+        self.hits_taken += 1;
+    }
+
+    fn next_action(&mut self, rng: &mut CombatRng) {
+        self.hits_taken = 0;
+        match self.pattern.next(rng) {
+            EnemyAction::Block => self.fighter.enter(FighterState::Blocking, BLOCK_DURATION),
+            EnemyAction::Punch(side) => {
+                self.side = side;
+                self.feint = false;
+                self.fighter.enter(FighterState::WindUp(side), PUNCH_WARNING_DURATION);
+            },
+            // wind up like a punch, then stand down
+            EnemyAction::Feint(side) => {
+                self.side = side;
+                self.feint = true;
+                self.fighter.enter(FighterState::WindUp(side), PUNCH_WARNING_DURATION);
+            },
+            EnemyAction::Wait(secs) => self.fighter.enter(FighterState::Idle, secs),
         }
     }
 }
 
 const BLOCK_DURATION: f32 = 0.75;
 const PUNCH_WARNING_DURATION: f32 = 1.0;
-pub const PUNCH_DURATION: f32 = 0.5;
-pub const IS_HIT_TIMER: f32 = 0.5;
+const PUNCH_DURATION: f32 = 0.5;
+const IS_HIT_TIMER: f32 = 0.5;
 
 pub fn update_enemy_movement(
     time: Res<Time>,
-    mut enemy_query: Query<(&mut Enemy, &mut Transform, &mut TextureAtlasSprite)>,
-    player_query: Query<(&mut Player)>,
+    mut enemy_query: Query<(Entity, &mut Enemy, &mut SimTranslation, &mut TextureAtlasSprite)>,
+    mut attacks: EventWriter<AttackEvent>,
+    mut rng: ResMut<CombatRng>,
 ) {
-    let mut rng = rand::thread_rng();
-    'enemy_loop: for (mut enemy, mut transform, mut sprite) in &mut enemy_query {
-
-        for player in &player_query {
-
-            if enemy.hits_taken_total > 15 {
-                let state = enemy.states.death;
-                enemy.animator.set_indices(state);
-                enemy.animator.set_frametime(0.1);
-                enemy.animator.loops = false;
-                sprite.index = enemy.animator.index;
-                enemy.animator.tick(time.delta()); 
-                break 'enemy_loop;
-            }
-
-            if player::PUNCH_DURATION - player.punch_timer < player::PUNCH_DURATION / 4. && player.punch_timer >= 0. && enemy.hits_taken < 2 {
-                if enemy.block_timer == 0. && enemy.is_hit_timer <= 0. {
-                    let state = enemy.states.hit;
-                    enemy.animator.set_indices(state);
-                    enemy.animator.set_frametime(0.1);
-                    enemy.animator.loops = false;
-                    enemy.is_hit_timer = IS_HIT_TIMER;
-                    enemy.punch_timer = PUNCH_WARNING_DURATION + PUNCH_DURATION;
-                    enemy.hits_taken += 1;
-                    enemy.hits_taken_total += 1;
-
-                    sprite.index = enemy.animator.index;
-                    enemy.animator.tick(time.delta()); 
-                    continue 'enemy_loop;
-                }
-            }
-
-            match (enemy.block_timer, enemy.punch_timer, enemy.wait_timer, enemy.is_hit_timer) {
-
-                (block_timer, punch_timer, wait_timer, is_hit_timer) if block_timer <= 0. && punch_timer <= 0. && wait_timer <= 0. && is_hit_timer <= 0. => {
-                    transform.translation = enemy.origin;
-    
-                    let mut action: u8 = rng.gen();
-                    action %= 6;
-    
-                    match action {
-                        // block
-                        1 => {
-                            let state = enemy.states.block;
-                            enemy.animator.set_indices(state);
-                            enemy.animator.set_frametime(0.1);
-                            enemy.animator.loops = false;
-                            enemy.block_timer = BLOCK_DURATION;
-                        },
-                        // left punch
-                        2 => {
-                            sprite.flip_x = false;
-                            let state = enemy.states.punch_warning;
-                            enemy.animator.set_indices(state);
-                            enemy.animator.set_frametime(0.2);
-                            enemy.animator.loops = true;
-                            enemy.action_dir = ActionDirection::Left;
-                            enemy.punch_timer = PUNCH_WARNING_DURATION + PUNCH_DURATION;
-                        },
-                        // right punch
-                        3 => {
-                            sprite.flip_x = true;
-                            let state = enemy.states.punch_warning;
-                            enemy.animator.set_indices(state);
-                            enemy.animator.set_frametime(0.2);
-                            enemy.animator.loops = true;
-                            enemy.action_dir = ActionDirection::Right;
-                            enemy.punch_timer = PUNCH_WARNING_DURATION + PUNCH_DURATION;
-                        },
-                        // nothing
-                        _ => {
-                            let state = enemy.states.idle;
-                            enemy.animator.set_indices(state);
-                            enemy.animator.set_frametime(0.3);
-                            enemy.animator.loops = true;
-                            enemy.block_timer = 0.0;
-                            enemy.wait_timer = 1.5;
-                        },
-                    }
-    
-                },
-                (_, _, _, is_hit_timer) if is_hit_timer > 0. => {
-                    if enemy.is_hit_timer < IS_HIT_TIMER {
-                        let state = enemy.states.hit;
-                        enemy.animator.set_indices(state);
-                        enemy.animator.set_frametime(0.3);
-                    }
-                    enemy.is_hit_timer -= time.delta_seconds()
+    for (entity, mut enemy, mut translation, mut sprite) in &mut enemy_query {
+        enemy.fighter.check_ko();
+
+        if enemy.fighter.finished() {
+            match enemy.fighter.state {
+                // punch back once the stun wears off
+                FighterState::Hit => {
+                    let side = enemy.side;
+                    enemy.feint = false;
+                    enemy.fighter.enter(FighterState::WindUp(side), PUNCH_WARNING_DURATION);
                 },
-                (block_timer, punch_timer, _, is_hit_timer) if block_timer > 0. && punch_timer <= 0. && is_hit_timer <= 0. => {
+                FighterState::WindUp(side) if !enemy.feint => {
                     enemy.hits_taken = 0;
-                    enemy.is_hit_timer = 0.;
-
-                    enemy.block_timer -= time.delta_seconds()
-                },
-                (_, punch_timer, _, is_hit_timer) if punch_timer > 0. && is_hit_timer <= 0. => {
-
-                    if enemy.punch_timer < PUNCH_DURATION {
-                        enemy.hits_taken = 0;
-                        enemy.is_hit_timer = 0.;
-
-                        let state = enemy.states.punch;
-                        enemy.animator.set_indices(state);
-                        enemy.animator.set_frametime(0.3);
-                        let x = (enemy.punch_timer / PUNCH_DURATION) * 2.0 - 1.0;
-                        let x = -x*x*x*x + 1.0;
-                        transform.translation = enemy.origin
-                            + Vec3::new(
-                                match enemy.action_dir {
-                                    ActionDirection::None => 0.0,
-                                    ActionDirection::Left => x - 1.0,
-                                    ActionDirection::Right => -x + 1.0,
-                                } * 5.0,
-                                x * -60.0,
-                                0.0,
-                            );
-                    }
-    
-                    enemy.punch_timer -= time.delta_seconds();
+                    enemy.fighter.enter(FighterState::Punching(side), PUNCH_DURATION);
                 },
-                (_, _, wait_timer, is_hit_timer) if wait_timer > 0. && is_hit_timer <= 0. => {
-                    enemy.hits_taken = 0;
-                    enemy.is_hit_timer = 0.;
+                FighterState::Down | FighterState::KO => {},
+                _ => enemy.next_action(&mut rng),
+            }
+        }
 
-                    enemy.wait_timer -= time.delta_seconds()
+        match enemy.fighter.state {
+            FighterState::WindUp(side) => sprite.flip_x = side == Side::Right,
+            FighterState::Punching(side) => {
+                sprite.flip_x = side == Side::Right;
+                // first tick of the punch, including the opening jab
+                if enemy.fighter.progress(PUNCH_DURATION) == 0. {
+                    attacks.send(AttackEvent {
+                        attacker: entity,
+                        side,
+                        damage: enemy.stats.punch_damage,
+                        window: PUNCH_DURATION,
+                    });
                 }
-                _ => {}
-                
-            }
-            sprite.index = enemy.animator.index;
-            enemy.animator.tick(time.delta());
+                let x = (enemy.fighter.timer / PUNCH_DURATION) * 2.0 - 1.0;
+                let x = -x*x*x*x + 1.0;
+                enemy.fighter.offset = Vec3::new(side.sign() * (1.0 - x) * 5.0, x * -60.0, 0.0);
+            },
+            _ => {}
         }
+
+        let fighter = &mut enemy.fighter;
+        translation.current = fighter.translation();
+        sprite.index = fighter.animator.index;
+        fighter.tick(time.delta());
     }
-}
\ No newline at end of file
+}
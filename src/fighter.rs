@@ -0,0 +1,166 @@
+//! State machine shared by the player and every enemy.
+
+use bevy::math::Vec3;
+use serde::Deserialize;
+
+use crate::{attack::Defense, util::*};
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+impl Side {
+    /// -1 for left, 1 for right.
+    pub fn sign(self) -> f32 {
+        match self {
+            Side::Left => -1.0,
+            Side::Right => 1.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FighterState {
+    Idle,
+    Blocking,
+    Dodging(Side),
+    /// Telegraphing a punch that hasn't been thrown yet.
+    WindUp(Side),
+    Punching(Side),
+    Hit,
+    Down,
+    KO,
+}
+
+/// Picks the animation a fighter shows in each state.
+pub trait StateClips {
+    fn clip(&self, state: FighterState) -> SpriteClip;
+}
+
+pub struct Fighter<S> {
+    pub state: FighterState,
+    /// Seconds left in the current state. Counts below zero in states that
+    /// don't end on their own.
+    pub timer: f32,
+    pub origin: Vec3,
+    /// Where the current state has moved the fighter, relative to `origin`.
+    pub offset: Vec3,
+    pub animator: Animator,
+    pub states: S,
+    pub hits_taken_total: i32,
+    pub health: u32,
+    pub max_health: u32,
+    /// Times this fighter has gone down in the current round.
+    pub knockdowns: u32,
+}
+
+impl<S: StateClips> Fighter<S> {
+    pub fn new(origin: Vec3, states: S, max_health: u32) -> Fighter<S> {
+        Fighter {
+            state: FighterState::Idle,
+            timer: 0.0,
+            origin,
+            offset: Vec3::ZERO,
+            animator: Animator::from_clip(states.clip(FighterState::Idle)),
+            states,
+            hits_taken_total: 0,
+            health: max_health,
+            max_health,
+            knockdowns: 0,
+        }
+    }
+
+    /// Leaves the current state and starts `state` for `duration` seconds.
+    pub fn enter(&mut self, state: FighterState, duration: f32) {
+        self.exit();
+        self.state = state;
+        self.timer = duration;
+        self.animator.play(self.states.clip(state));
+    }
+
+    fn exit(&mut self) {
+        match self.state {
+            FighterState::Dodging(_) | FighterState::Punching(_) => self.offset = Vec3::ZERO,
+            _ => {}
+        }
+    }
+
+    pub fn finished(&self) -> bool {
+        self.timer <= 0.
+    }
+
+    /// How far through the current state we are, from 0 to 1.
+    pub fn progress(&self, duration: f32) -> f32 {
+        1.0 - (self.timer / duration)
+    }
+
+    pub fn is_out(&self) -> bool {
+        matches!(self.state, FighterState::Down | FighterState::KO)
+    }
+
+    pub fn defense(&self) -> Defense {
+        match self.state {
+            FighterState::Idle | FighterState::WindUp(_) | FighterState::Punching(_) => Defense::Open,
+            FighterState::Blocking => Defense::Blocking,
+            FighterState::Dodging(_) => Defense::Dodging,
+            FighterState::Hit | FighterState::Down | FighterState::KO => Defense::Untouchable,
+        }
+    }
+
+    /// Takes `damage` off health and starts `stun` seconds of hit-stun.
+    pub fn take_hit(&mut self, stun: f32, damage: u32) {
+        self.hits_taken_total += 1;
+        self.health = self.health.saturating_sub(damage);
+        self.enter(FighterState::Hit, stun);
+    }
+
+    /// Gives back `share` of the missing health.
+    pub fn recover(&mut self, share: f32) {
+        let missing = self.max_health - self.health;
+        self.health += (missing as f32 * share).round() as u32;
+    }
+
+    /// Stands back up at the origin, as at the start of a round.
+    pub fn reset(&mut self) {
+        self.enter(FighterState::Idle, 0.0);
+        self.offset = Vec3::ZERO;
+    }
+
+    /// Goes down once health runs out. The fighter stays down until
+    /// [`crate::knockdown`] either gets them up or counts them out.
+    pub fn check_ko(&mut self) {
+        if self.health == 0 && !self.is_out() {
+            self.enter(FighterState::Down, 0.0);
+        }
+    }
+
+    /// Back on their feet after a knockdown, with less health the more often
+    /// they've been down this round.
+    pub fn get_up(&mut self) {
+        self.health = self.max_health / (self.knockdowns + 1);
+        self.reset();
+    }
+
+    /// Swaps in freshly loaded clips, restarting the current state's animation.
+    pub fn set_states(&mut self, states: S) {
+        self.animator = Animator::from_clip(states.clip(self.state));
+        self.states = states;
+    }
+
+    /// Remaining health from 0 to 1, for the HUD.
+    pub fn health_fraction(&self) -> f32 {
+        self.health as f32 / self.max_health as f32
+    }
+
+    pub fn translation(&self) -> Vec3 {
+        self.origin + self.offset
+    }
+
+    /// Advances the state timer and animation by `delta` seconds.
+    pub fn tick(&mut self, delta: std::time::Duration) {
+        self.timer -= delta.as_secs_f32();
+        self.animator.tick(delta);
+    }
+}
@@ -0,0 +1,327 @@
+//! RON description of a fighter's sprite sheet and animation clips, loaded
+//! through the [`AssetServer`](bevy::asset::AssetServer) from `*.fighter.ron`.
+//!
+//! ```ron
+//! FighterDef(
+//!     name: "BETTY MERCY",
+//!     atlas: "sprites/atlases/betty_mercy.png",
+//!     frames: {
+//!         "idle_0": (1, 1, 24, 88),
+//!     },
+//!     clips: {
+//!         "idle": (frames: ["idle_0"], frame_time: 0.3, loops: true),
+//!     },
+//! )
+//! ```
+
+use std::{collections::HashMap, fmt};
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    prelude::*,
+    utils::BoxedFuture,
+};
+use ron::error::SpannedError;
+use serde::Deserialize;
+
+use crate::{
+    enemy::{Enemy, EnemyStates, EnemyStats},
+    loading::LoadErrors,
+    pattern::{AttackPattern, PatternStep},
+    player::{Player, PlayerStates},
+    util::*,
+};
+
+/// A frame on the sheet, given as `(x, y, width, height)` in pixels.
+#[derive(Deserialize, Clone, Copy)]
+pub struct FrameRect(pub f32, pub f32, pub f32, pub f32);
+
+#[derive(Deserialize, Clone)]
+pub struct ClipDef {
+    pub frames: Vec<String>,
+    pub frame_time: f32,
+    #[serde(default)]
+    pub loops: bool,
+}
+
+#[derive(Asset, TypePath, Deserialize, Clone)]
+pub struct FighterDef {
+    /// Shown on the circuit screen; left out, the file name is used.
+    #[serde(default)]
+    pub name: String,
+    pub atlas: String,
+    /// The sheet named by `atlas`, requested by the loader.
+    #[serde(skip)]
+    #[dependency]
+    pub image: Handle<Image>,
+    pub frames: HashMap<String, FrameRect>,
+    pub clips: HashMap<String, ClipDef>,
+    /// Attack pattern for enemies; left out, the enemy acts at random.
+    #[serde(default)]
+    pub pattern: Vec<PatternStep>,
+    /// Health, punch damage and grit for enemies.
+    #[serde(default)]
+    pub stats: EnemyStats,
+    /// Sounds the fighter makes; any left out are simply not played.
+    #[serde(default)]
+    pub voice: VoiceClips,
+}
+
+/// Paths of a fighter's voice clips. They aren't dependencies of the
+/// definition, so a missing clip never stops the game from loading.
+#[derive(Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct VoiceClips {
+    /// Throwing a punch.
+    pub punch: Option<String>,
+    /// Getting hit.
+    pub hit: Option<String>,
+    /// Going down.
+    pub down: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum FighterDefError {
+    Io(std::io::Error),
+    Parse(SpannedError),
+    MissingClip(String),
+    EmptyClip(String),
+    MissingFrame { clip: String, frame: String },
+    FrameOutOfBounds { frame: String, size: Vec2 },
+}
+
+impl fmt::Display for FighterDefError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FighterDefError::Io(err) => write!(f, "could not read fighter file: {err}"),
+            FighterDefError::Parse(err) => write!(
+                f,
+                "could not parse fighter file at {}:{}: {}",
+                err.position.line, err.position.col, err.code
+            ),
+            FighterDefError::MissingClip(clip) => write!(f, "fighter has no `{clip}` clip"),
+            FighterDefError::EmptyClip(clip) => write!(f, "clip `{clip}` has no frames"),
+            FighterDefError::MissingFrame { clip, frame } => {
+                write!(f, "clip `{clip}` uses undefined frame `{frame}`")
+            }
+            FighterDefError::FrameOutOfBounds { frame, size } => {
+                write!(f, "frame `{frame}` lies outside the {}x{} sheet", size.x, size.y)
+            }
+        }
+    }
+}
+
+impl std::error::Error for FighterDefError {}
+
+impl From<std::io::Error> for FighterDefError {
+    fn from(err: std::io::Error) -> Self {
+        FighterDefError::Io(err)
+    }
+}
+
+impl From<SpannedError> for FighterDefError {
+    fn from(err: SpannedError) -> Self {
+        FighterDefError::Parse(err)
+    }
+}
+
+impl FighterDef {
+    pub fn from_bytes(bytes: &[u8]) -> Result<FighterDef, FighterDefError> {
+        Ok(ron::de::from_bytes(bytes)?)
+    }
+
+    /// Lays every clip out as a contiguous run of atlas frames, so each one maps
+    /// onto a single [`AnimationIndices`] range. Frames shared between clips are
+    /// added once per clip. `size` is the size of the loaded sheet.
+    pub fn build_atlas(&self, size: Vec2) -> Result<(TextureAtlas, FighterClips), FighterDefError> {
+        let mut atlas = TextureAtlas::new_empty(self.image.clone(), size);
+        let mut clips = HashMap::new();
+
+        // sorted so the atlas layout doesn't depend on hash order
+        let mut names: Vec<&String> = self.clips.keys().collect();
+        names.sort();
+
+        for name in names {
+            let clip = &self.clips[name];
+            if clip.frames.is_empty() {
+                return Err(FighterDefError::EmptyClip(name.clone()));
+            }
+            let first = atlas.len();
+            for frame in &clip.frames {
+                let FrameRect(x, y, w, h) = *self.frames.get(frame).ok_or_else(|| {
+                    FighterDefError::MissingFrame {
+                        clip: name.clone(),
+                        frame: frame.clone(),
+                    }
+                })?;
+                if x + w > size.x || y + h > size.y {
+                    return Err(FighterDefError::FrameOutOfBounds {
+                        frame: frame.clone(),
+                        size,
+                    });
+                }
+                atlas.add_texture(AtlasUtil::from_corner_size(x, y, w, h));
+            }
+            clips.insert(
+                name.clone(),
+                SpriteClip {
+                    indices: AnimationIndices {
+                        first,
+                        last: atlas.len() - 1,
+                    },
+                    frame_time: clip.frame_time,
+                    loops: clip.loops,
+                },
+            );
+        }
+
+        Ok((atlas, FighterClips(clips)))
+    }
+}
+
+/// Clips of a built fighter, keyed by name.
+pub struct FighterClips(pub HashMap<String, SpriteClip>);
+
+impl FighterClips {
+    pub fn get(&self, name: &str) -> Result<SpriteClip, FighterDefError> {
+        self.0
+            .get(name)
+            .copied()
+            .ok_or_else(|| FighterDefError::MissingClip(name.to_string()))
+    }
+}
+
+pub struct FighterDefLoader {
+    errors: LoadErrors,
+}
+
+impl FromWorld for FighterDefLoader {
+    fn from_world(world: &mut World) -> Self {
+        FighterDefLoader {
+            errors: world.get_resource_or_insert_with(LoadErrors::default).clone(),
+        }
+    }
+}
+
+impl AssetLoader for FighterDefLoader {
+    type Asset = FighterDef;
+    type Settings = ();
+    type Error = FighterDefError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<FighterDef, FighterDefError>> {
+        Box::pin(async move {
+            let result = async {
+                let mut bytes = Vec::new();
+                reader.read_to_end(&mut bytes).await?;
+                let mut def = FighterDef::from_bytes(&bytes)?;
+                def.image = load_context.load(&def.atlas);
+                Ok(def)
+            }
+            .await;
+            self.errors.record(load_context.path(), result)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["fighter.ron"]
+    }
+}
+
+/// Which side a [`FighterSource`] builds once its definition is available.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FighterRole {
+    Player,
+    Enemy,
+}
+
+/// Links a fighter entity to the definition it is built from. The
+/// [`Player`]/[`Enemy`] component is inserted when the definition first loads
+/// and has its clips swapped whenever the file changes on disk.
+#[derive(Component)]
+pub struct FighterSource {
+    pub def: Handle<FighterDef>,
+    pub role: FighterRole,
+    pub origin: Vec3,
+}
+
+type FighterSourceQuery<'a> = (
+    Entity,
+    Ref<'a, FighterSource>,
+    &'a mut Handle<TextureAtlas>,
+    &'a mut TextureAtlasSprite,
+    Option<&'a mut Player>,
+    Option<&'a mut Enemy>,
+);
+
+pub fn apply_fighter_defs(
+    mut commands: Commands,
+    mut events: EventReader<AssetEvent<FighterDef>>,
+    defs: Res<Assets<FighterDef>>,
+    images: Res<Assets<Image>>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    mut fighters: Query<FighterSourceQuery>,
+) {
+    let changed: Vec<AssetId<FighterDef>> = events
+        .read()
+        .filter_map(|event| match event {
+            AssetEvent::Added { id } | AssetEvent::Modified { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
+
+    for (entity, source, mut atlas_handle, mut sprite, player, enemy) in &mut fighters {
+        // fighters spawned after their definition loaded never see an event
+        if !changed.contains(&source.def.id()) && !source.is_added() {
+            continue;
+        }
+        let Some(def) = defs.get(&source.def) else {
+            continue;
+        };
+        let Some(image) = images.get(&def.image) else {
+            continue;
+        };
+        let applied = def.build_atlas(image.size_f32()).and_then(|(atlas, clips)| {
+            let idle = match source.role {
+                FighterRole::Player => {
+                    let states = PlayerStates::from_clips(&clips)?;
+                    let idle = states.idle;
+                    match player {
+                        Some(mut player) => player.fighter.set_states(states),
+                        None => {
+                            commands.entity(entity).insert(Player::new(source.origin, states));
+                        }
+                    }
+                    idle
+                }
+                FighterRole::Enemy => {
+                    let states = EnemyStates::from_clips(&clips)?;
+                    let idle = states.idle;
+                    let pattern = AttackPattern::new(def.pattern.clone());
+                    match enemy {
+                        Some(mut enemy) => {
+                            enemy.fighter.set_states(states);
+                            enemy.set_pattern(pattern);
+                            enemy.set_stats(def.stats);
+                        }
+                        None => {
+                            commands.entity(entity).insert(Enemy::new(source.origin, states, pattern, def.stats));
+                        }
+                    }
+                    idle
+                }
+            };
+            *atlas_handle = texture_atlases.add(atlas);
+            sprite.index = idle.indices.first;
+            Ok(())
+        });
+        if let Err(err) = applied {
+            let path = source.def.path().map(ToString::to_string).unwrap_or_default();
+            error!("{path}: {err}");
+        }
+    }
+}
@@ -0,0 +1,245 @@
+//! The overlay printed on the stage: the heart counter, the score, both
+//! health bars, the round clock and the intermission screen. Positions line
+//! up with the slots drawn into `sprites/stage.png`.
+
+use bevy::{prelude::*, sprite::Anchor};
+
+use crate::{
+    enemy::Enemy,
+    fighter_def::FighterRole,
+    knockdown::Count,
+    player::Player,
+    round::{Round, RoundPhase},
+    score::Score,
+    state::FightEntity,
+};
+
+#[derive(Component)]
+pub struct HeartText;
+
+/// The player's points, in the POINTS box.
+#[derive(Component)]
+pub struct ScoreText;
+
+#[derive(Component)]
+pub struct HealthBar(pub FighterRole);
+
+#[derive(Component)]
+pub struct ClockText;
+
+#[derive(Component)]
+pub struct RoundText;
+
+/// Shown between rounds and after the last one.
+#[derive(Component)]
+pub struct IntermissionScreen;
+
+#[derive(Component)]
+pub struct IntermissionText;
+
+/// The referee's count, shown in the middle of the ring.
+#[derive(Component)]
+pub struct CountText;
+
+const BAR_SIZE: Vec2 = Vec2::new(96.0, 14.0);
+const BAR_COLOR: Color = Color::rgb(0.99, 0.99, 0.99);
+/// Background of the clock and points boxes, used to paint over the
+/// placeholders printed on the stage.
+const CLOCK_BOX_COLOR: Color = Color::rgb(139.0 / 255.0, 98.0 / 255.0, 132.0 / 255.0);
+
+pub fn spawn_health_bars(commands: &mut Commands) {
+    // left edges of the two slots; the player's bar is on the left
+    for (role, x) in [(FighterRole::Player, -80.0), (FighterRole::Enemy, 32.0)] {
+        commands.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: BAR_COLOR,
+                    custom_size: Some(BAR_SIZE),
+                    anchor: Anchor::CenterLeft,
+                    ..default()
+                },
+                transform: Transform::from_translation(Vec3::new(x, 185.0, 0.0)),
+                ..default()
+            },
+            HealthBar(role),
+            FightEntity,
+        ));
+    }
+}
+
+pub fn spawn_round_hud(commands: &mut Commands, asset_server: &AssetServer) {
+    let style = TextStyle {
+        font: asset_server.load("fonts/font.ttf"),
+        font_size: 16.0,
+        color: Color::WHITE,
+    };
+    commands.spawn((
+        SpriteBundle {
+            sprite: Sprite {
+                color: CLOCK_BOX_COLOR,
+                custom_size: Some(Vec2::new(68.0, 18.0)),
+                ..default()
+            },
+            transform: Transform::from_translation(Vec3::new(191.0, 183.0, 0.0)),
+            ..default()
+        },
+        FightEntity,
+    ));
+    commands.spawn((
+        Text2dBundle {
+            text: Text::from_section("", style.clone()),
+            transform: Transform::from_translation(Vec3::new(191.0, 183.0, 0.1)),
+            ..default()
+        },
+        ClockText,
+        FightEntity,
+    ));
+    // the stage prints "NO" where the points go
+    commands.spawn((
+        SpriteBundle {
+            sprite: Sprite {
+                color: CLOCK_BOX_COLOR,
+                custom_size: Some(Vec2::new(106.0, 18.0)),
+                ..default()
+            },
+            transform: Transform::from_translation(Vec3::new(81.0, 168.0, 0.0)),
+            ..default()
+        },
+        FightEntity,
+    ));
+    commands.spawn((
+        Text2dBundle {
+            text: Text::from_section("", style.clone()),
+            text_anchor: Anchor::CenterRight,
+            transform: Transform::from_translation(Vec3::new(128.0, 168.0, 0.1)),
+            ..default()
+        },
+        ScoreText,
+        FightEntity,
+    ));
+    commands.spawn((
+        Text2dBundle {
+            text: Text::from_section("", style.clone()),
+            transform: Transform::from_translation(Vec3::new(222.0, 166.0, 0.1)),
+            ..default()
+        },
+        RoundText,
+        FightEntity,
+    ));
+    commands.spawn((
+        Text2dBundle {
+            text: Text::from_section("", TextStyle { font_size: 48.0, ..style.clone() }),
+            transform: Transform::from_translation(Vec3::new(0.0, 40.0, 4.0)),
+            ..default()
+        },
+        CountText,
+        FightEntity,
+    ));
+    commands
+        .spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: Color::rgba(0.0, 0.0, 0.0, 0.75),
+                    custom_size: Some(Vec2::new(512.0, 448.0)),
+                    ..default()
+                },
+                transform: Transform::from_translation(Vec3::new(0.0, 0.0, 5.0)),
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            IntermissionScreen,
+            FightEntity,
+        ))
+        .with_children(|screen| {
+            screen.spawn((
+                Text2dBundle {
+                    text: Text::from_section("", style).with_alignment(TextAlignment::Center),
+                    transform: Transform::from_translation(Vec3::new(0.0, 0.0, 0.1)),
+                    ..default()
+                },
+                IntermissionText,
+            ));
+        });
+}
+
+pub fn update_heart_text(player_query: Query<&Player>, mut text_query: Query<&mut Text, With<HeartText>>) {
+    let Ok(player) = player_query.get_single() else {
+        return;
+    };
+    for mut text in &mut text_query {
+        text.sections[0].value = format!("{:02}", player.hearts);
+    }
+}
+
+pub fn update_score_text(score: Res<Score>, mut text_query: Query<&mut Text, With<ScoreText>>) {
+    for mut text in &mut text_query {
+        text.sections[0].value = score.points.to_string();
+    }
+}
+
+pub fn update_health_bars(
+    player_query: Query<&Player>,
+    enemy_query: Query<&Enemy>,
+    mut bar_query: Query<(&HealthBar, &mut Sprite)>,
+) {
+    for (bar, mut sprite) in &mut bar_query {
+        let fraction = match bar.0 {
+            FighterRole::Player => player_query.get_single().map(|player| player.fighter.health_fraction()),
+            FighterRole::Enemy => enemy_query.get_single().map(|enemy| enemy.fighter.health_fraction()),
+        };
+        if let Ok(fraction) = fraction {
+            sprite.custom_size = Some(Vec2::new(BAR_SIZE.x * fraction, BAR_SIZE.y));
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub fn update_round_hud(
+    round: Res<Round>,
+    mut clock_query: Query<&mut Text, (With<ClockText>, Without<RoundText>, Without<IntermissionText>)>,
+    mut round_query: Query<&mut Text, (With<RoundText>, Without<IntermissionText>)>,
+    mut intermission_text_query: Query<&mut Text, With<IntermissionText>>,
+    mut screen_query: Query<&mut Visibility, With<IntermissionScreen>>,
+) {
+    for mut text in &mut clock_query {
+        text.sections[0].value = round.clock();
+    }
+    for mut text in &mut round_query {
+        text.sections[0].value = round.number.to_string();
+    }
+
+    let message = match round.phase {
+        RoundPhase::Intermission => Some(format!(
+            "END OF ROUND {}\n\nROUND {} NEXT",
+            round.number,
+            round.number + 1
+        )),
+        RoundPhase::Over => Some(match round.result {
+            Some(result) => format!(
+                "{}\n\n{}",
+                result.finish.label(),
+                if result.winner == FighterRole::Player { "YOU WIN" } else { "YOU LOSE" }
+            ),
+            None => "FIGHT OVER".to_string(),
+        }),
+        RoundPhase::Fighting | RoundPhase::Bell | RoundPhase::Knockdown => None,
+    };
+    for mut visibility in &mut screen_query {
+        *visibility = if message.is_some() { Visibility::Visible } else { Visibility::Hidden };
+    }
+    if let Some(message) = message {
+        for mut text in &mut intermission_text_query {
+            text.sections[0].value = message.clone();
+        }
+    }
+}
+
+pub fn update_count_text(count: Option<Res<Count>>, mut text_query: Query<&mut Text, With<CountText>>) {
+    let value = match count {
+        Some(count) if count.count > 0 => count.count.to_string(),
+        _ => String::new(),
+    };
+    for mut text in &mut text_query {
+        text.sections[0].value = value.clone();
+    }
+}
@@ -0,0 +1,85 @@
+//! Making clean hits feel heavy. A hit-stop freezes the whole fight for a
+//! few ticks after a punch lands, and the camera shakes in proportion to the
+//! damage. Both are set from [`Settings`] and can be turned off there.
+//!
+//! Hit-stopped ticks are skipped entirely, replay recording and playback
+//! included, so a replay plays back the same whatever the setting.
+
+use bevy::prelude::*;
+
+use crate::{attack::HitEvent, save::Settings};
+
+/// How long a shake takes to die down, in seconds.
+const SHAKE_DURATION: f32 = 0.25;
+/// Pixels of shake per point of damage, at full strength.
+const SHAKE_PER_DAMAGE: f32 = 0.5;
+
+/// Ticks to freeze after each clean hit. Off unless set.
+#[derive(Resource, Default, Debug)]
+pub struct HitStop {
+    pub frames: u32,
+    remaining: u32,
+    /// Whether the current tick is frozen.
+    frozen: bool,
+}
+
+impl HitStop {
+    /// Ends a hit-stop in progress.
+    pub fn clear(&mut self) {
+        self.remaining = 0;
+        self.frozen = false;
+    }
+}
+
+/// Shakes the camera it is on; at rest it sits at the origin.
+#[derive(Component, Default)]
+pub struct CameraShake {
+    strength: f32,
+    remaining: f32,
+}
+
+/// Runs first thing every fighting tick, before anything that may be frozen.
+pub fn count_down_hit_stop(mut stop: ResMut<HitStop>) {
+    stop.frozen = stop.remaining > 0;
+    stop.remaining = stop.remaining.saturating_sub(1);
+}
+
+pub fn not_hit_stopped(stop: Res<HitStop>) -> bool {
+    !stop.frozen
+}
+
+pub fn start_hit_stop(mut stop: ResMut<HitStop>, mut hits: EventReader<HitEvent>) {
+    if hits.read().count() > 0 {
+        stop.remaining = stop.frames;
+    }
+}
+
+pub fn apply_impact_settings(settings: Res<Settings>, mut stop: ResMut<HitStop>) {
+    stop.frames = settings.hit_stop_frames;
+}
+
+pub fn shake_camera(
+    time: Res<Time>,
+    settings: Res<Settings>,
+    mut hits: EventReader<HitEvent>,
+    mut cameras: Query<(&mut CameraShake, &mut Transform)>,
+) {
+    let strength = hits.read().map(|hit| hit.damage as f32 * SHAKE_PER_DAMAGE).fold(0., f32::max)
+        * settings.screen_shake;
+    for (mut shake, mut transform) in &mut cameras {
+        if strength > 0. {
+            shake.strength = shake.strength.max(strength);
+            shake.remaining = SHAKE_DURATION;
+        }
+        shake.remaining = (shake.remaining - time.delta_seconds()).max(0.);
+        let fade = shake.remaining / SHAKE_DURATION;
+        // two unrelated frequencies wander without needing the combat rng
+        let t = time.elapsed_seconds();
+        let offset = Vec2::new((t * 73.).sin(), (t * 91.).cos()) * shake.strength * fade;
+        if shake.remaining == 0. {
+            shake.strength = 0.;
+        }
+        transform.translation.x = offset.x.round();
+        transform.translation.y = offset.y.round();
+    }
+}
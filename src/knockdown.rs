@@ -0,0 +1,168 @@
+//! Knockdowns and the referee's count. A fighter whose health runs out goes
+//! down and the round clock stops while the referee counts, one number a
+//! second. The player gets up by mashing punch; an enemy decides as it falls
+//! whether, and on which count, it will rise. A third knockdown in a round
+//! is a TKO and a count of ten is a KO.
+
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::{
+    attack::ActiveAttacks,
+    controls::PlayerInput,
+    enemy::Enemy,
+    fighter::FighterState,
+    fighter_def::FighterRole,
+    player::Player,
+    rng::CombatRng,
+    round::{Finish, Round, RoundPhase},
+};
+
+const COUNT_INTERVAL: f32 = 1.0;
+const KO_COUNT: u32 = 10;
+const TKO_KNOCKDOWNS: u32 = 3;
+/// Punch presses the player needs to get up, per knockdown so far this round.
+const MASHES_PER_KNOCKDOWN: u32 = 8;
+
+/// Present while someone is down.
+#[derive(Resource, Debug)]
+pub struct Count {
+    pub fighter: Entity,
+    pub role: FighterRole,
+    /// The last number called, 0 until the first second has passed.
+    pub count: u32,
+    timer: f32,
+    mashes: u32,
+    /// The count an enemy rises on, if it rises at all.
+    rises_at: Option<u32>,
+}
+
+impl Count {
+    fn new(fighter: Entity, role: FighterRole, rises_at: Option<u32>) -> Count {
+        Count {
+            fighter,
+            role,
+            count: 0,
+            timer: COUNT_INTERVAL,
+            mashes: 0,
+            rises_at,
+        }
+    }
+}
+
+#[derive(Event, Clone, Copy, Debug)]
+pub struct KnockdownEvent {
+    pub fighter: Entity,
+}
+
+/// A number called by the referee.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct CountEvent(pub u32);
+
+#[allow(clippy::too_many_arguments)]
+pub fn start_knockdown(
+    mut commands: Commands,
+    mut round: ResMut<Round>,
+    mut attacks: ResMut<ActiveAttacks>,
+    mut rng: ResMut<CombatRng>,
+    mut knockdowns: EventWriter<KnockdownEvent>,
+    mut player_query: Query<(Entity, &mut Player)>,
+    mut enemy_query: Query<(Entity, &mut Enemy)>,
+) {
+    let mut count = None;
+    for (entity, mut player) in &mut player_query {
+        if player.fighter.state == FighterState::Down {
+            player.fighter.knockdowns += 1;
+            count = Some((Count::new(entity, FighterRole::Player, None), player.fighter.knockdowns));
+        }
+    }
+    for (entity, mut enemy) in &mut enemy_query {
+        if enemy.fighter.state == FighterState::Down && count.is_none() {
+            enemy.fighter.knockdowns += 1;
+            let chance = enemy.stats.get_up_chance.powi(enemy.fighter.knockdowns as i32);
+            let rises_at = if rng.gen::<f32>() < chance {
+                Some(rng.gen_range(2..KO_COUNT))
+            } else {
+                None
+            };
+            count = Some((Count::new(entity, FighterRole::Enemy, rises_at), enemy.fighter.knockdowns));
+        }
+    }
+    let Some((count, times)) = count else {
+        return;
+    };
+
+    attacks.clear();
+    knockdowns.send(KnockdownEvent { fighter: count.fighter });
+    if times >= TKO_KNOCKDOWNS {
+        knock_out(&mut round, &count, Finish::TKO, &mut player_query, &mut enemy_query);
+        return;
+    }
+    round.phase = RoundPhase::Knockdown;
+    commands.insert_resource(count);
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn update_count(
+    mut commands: Commands,
+    time: Res<Time>,
+    input: Res<PlayerInput>,
+    mut count: ResMut<Count>,
+    mut round: ResMut<Round>,
+    mut counts: EventWriter<CountEvent>,
+    mut player_query: Query<(Entity, &mut Player)>,
+    mut enemy_query: Query<(Entity, &mut Enemy)>,
+) {
+    if input.punch_left || input.punch_right {
+        count.mashes += 1;
+    }
+    count.timer -= time.delta_seconds();
+    if count.timer <= 0. {
+        count.count += 1;
+        count.timer += COUNT_INTERVAL;
+        counts.send(CountEvent(count.count));
+    }
+
+    if count.count >= KO_COUNT {
+        knock_out(&mut round, &count, Finish::KO, &mut player_query, &mut enemy_query);
+        commands.remove_resource::<Count>();
+        return;
+    }
+
+    let gets_up = match count.role {
+        FighterRole::Player => player_query
+            .get(count.fighter)
+            .is_ok_and(|(_, player)| count.mashes >= MASHES_PER_KNOCKDOWN * player.fighter.knockdowns),
+        FighterRole::Enemy => count.rises_at.is_some_and(|rises_at| count.count >= rises_at),
+    };
+    if gets_up {
+        if let Ok((_, mut player)) = player_query.get_mut(count.fighter) {
+            player.fighter.get_up();
+        }
+        if let Ok((_, mut enemy)) = enemy_query.get_mut(count.fighter) {
+            enemy.fighter.get_up();
+        }
+        round.phase = RoundPhase::Fighting;
+        commands.remove_resource::<Count>();
+    }
+}
+
+fn knock_out(
+    round: &mut Round,
+    count: &Count,
+    finish: Finish,
+    player_query: &mut Query<(Entity, &mut Player)>,
+    enemy_query: &mut Query<(Entity, &mut Enemy)>,
+) {
+    if let Ok((_, mut player)) = player_query.get_mut(count.fighter) {
+        player.fighter.enter(FighterState::KO, 0.0);
+    }
+    if let Ok((_, mut enemy)) = enemy_query.get_mut(count.fighter) {
+        enemy.fighter.enter(FighterState::KO, 0.0);
+    }
+    let winner = match count.role {
+        FighterRole::Player => FighterRole::Enemy,
+        FighterRole::Enemy => FighterRole::Player,
+    };
+    round.finish(winner, finish);
+}
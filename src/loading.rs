@@ -0,0 +1,181 @@
+//! The `Loading` state: requests everything a fight needs up front and waits
+//! for all of it, including the sheets that fighter definitions point at.
+//! Anything that fails to load, or a fighter definition that doesn't fit its
+//! sheet, is listed on screen instead of moving on.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use bevy::{
+    asset::{LoadState, RecursiveDependencyLoadState, UntypedAssetId},
+    prelude::*,
+};
+
+use crate::{
+    enemy::EnemyStates,
+    fighter_def::{FighterDef, FighterDefError, FighterRole},
+    player::PlayerStates,
+    roster::Roster,
+    state::{AppState, ScreenEntity},
+};
+
+/// Handles to every asset a fight uses, kept alive for the whole session.
+#[derive(Resource)]
+pub struct GameAssets {
+    pub player: Handle<FighterDef>,
+    pub roster: Handle<Roster>,
+    pub stage: Handle<Image>,
+    pub font: Handle<Font>,
+}
+
+/// The last error each of our own loaders hit, by asset path. The asset
+/// server only logs why a load failed, so the fighter and roster loaders keep
+/// a copy here for the loading screen.
+#[derive(Resource, Clone, Default)]
+pub struct LoadErrors(Arc<Mutex<HashMap<PathBuf, String>>>);
+
+impl LoadErrors {
+    /// Stores the error from loading `path`, or clears it once it loads again.
+    pub fn record<T, E: fmt::Display>(&self, path: &Path, result: Result<T, E>) -> Result<T, E> {
+        let mut errors = self.0.lock().unwrap();
+        match &result {
+            Ok(_) => errors.remove(path),
+            Err(err) => errors.insert(path.to_path_buf(), err.to_string()),
+        };
+        result
+    }
+
+    pub fn get(&self, path: &Path) -> Option<String> {
+        self.0.lock().unwrap().get(path).cloned()
+    }
+}
+
+#[derive(Component)]
+pub struct LoadingText;
+
+pub fn start_loading(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(GameAssets {
+        player: asset_server.load("fighters/betty_mercy.fighter.ron"),
+        roster: asset_server.load("circuits.roster.ron"),
+        stage: asset_server.load("sprites/stage.png"),
+        font: asset_server.load("fonts/font.ttf"),
+    });
+    // the game font may be what's missing, so this uses the built-in one
+    commands.spawn((
+        Text2dBundle {
+            text: Text::from_section("LOADING...", TextStyle::default()).with_alignment(TextAlignment::Center),
+            ..default()
+        },
+        LoadingText,
+        ScreenEntity,
+    ));
+}
+
+/// Moves on to the title once everything is in, or lists what went wrong.
+#[allow(clippy::too_many_arguments)]
+pub fn check_loading(
+    asset_server: Res<AssetServer>,
+    assets: Res<GameAssets>,
+    load_errors: Res<LoadErrors>,
+    rosters: Res<Assets<Roster>>,
+    defs: Res<Assets<FighterDef>>,
+    images: Res<Assets<Image>>,
+    mut next_state: ResMut<NextState<AppState>>,
+    mut text_query: Query<&mut Text, With<LoadingText>>,
+    mut reported: Local<bool>,
+) {
+    if *reported {
+        return;
+    }
+
+    // opponents are only known once the roster itself is in
+    let mut fighters = vec![(assets.player.clone(), FighterRole::Player)];
+    if let Some(roster) = rosters.get(&assets.roster) {
+        fighters.extend(roster.opponents().map(|def| (def.clone(), FighterRole::Enemy)));
+    }
+    let roster_id = assets.roster.id().untyped();
+    let ids: Vec<UntypedAssetId> = [roster_id, assets.stage.id().untyped(), assets.font.id().untyped()]
+        .into_iter()
+        .chain(fighters.iter().map(|(def, _)| def.id().untyped()))
+        .collect();
+
+    let mut problems = Vec::new();
+    let mut waiting = false;
+    for id in ids {
+        let asset_path = asset_server.get_path(id);
+        let path = asset_path.as_ref().map(ToString::to_string).unwrap_or_default();
+        match (asset_server.load_state(id), asset_server.recursive_dependency_load_state(id)) {
+            (LoadState::Failed, _) => {
+                // a file that doesn't exist never reaches our loaders
+                let err = asset_path
+                    .and_then(|asset_path| load_errors.get(asset_path.path()))
+                    .unwrap_or_else(|| "missing or unreadable".to_string());
+                problems.push(format!("{path}: {err}"));
+            }
+            // whichever opponent failed is reported on its own
+            (_, RecursiveDependencyLoadState::Failed) if id == roster_id => {}
+            (_, RecursiveDependencyLoadState::Failed) => {
+                // fighter definitions only depend on their sheet
+                let sheet = fighters
+                    .iter()
+                    .find(|(def, _)| def.id().untyped() == id)
+                    .and_then(|(def, _)| defs.get(def))
+                    .map(|def| def.atlas.clone())
+                    .unwrap_or_default();
+                problems.push(format!("{sheet}: missing or unreadable (used by {path})"));
+            }
+            (LoadState::Loaded, RecursiveDependencyLoadState::Loaded) => {}
+            _ => waiting = true,
+        }
+    }
+    if waiting && problems.is_empty() {
+        return;
+    }
+
+    if problems.is_empty() {
+        for (handle, role) in &fighters {
+            if let Err(err) = validate(handle, *role, &defs, &images) {
+                let path = handle.path().map(ToString::to_string).unwrap_or_default();
+                problems.push(format!("{path}: {err}"));
+            }
+        }
+    }
+    if problems.is_empty() {
+        next_state.set(AppState::Title);
+        return;
+    }
+
+    for problem in &problems {
+        error!("{problem}");
+    }
+    for mut text in &mut text_query {
+        text.sections[0].value = format!("COULD NOT LOAD\n\n{}", problems.join("\n"));
+        text.sections[0].style.color = Color::RED;
+    }
+    *reported = true;
+}
+
+/// Builds the fighter once up front, so a broken definition shows here
+/// rather than as an empty ring.
+fn validate(
+    handle: &Handle<FighterDef>,
+    role: FighterRole,
+    defs: &Assets<FighterDef>,
+    images: &Assets<Image>,
+) -> Result<(), FighterDefError> {
+    let Some(def) = defs.get(handle) else {
+        return Ok(());
+    };
+    let Some(image) = images.get(&def.image) else {
+        return Ok(());
+    };
+    let (_, clips) = def.build_atlas(image.size_f32())?;
+    match role {
+        FighterRole::Player => PlayerStates::from_clips(&clips).map(|_| ()),
+        FighterRole::Enemy => EnemyStates::from_clips(&clips).map(|_| ()),
+    }
+}
@@ -1,58 +1,199 @@
 //! Renders an animated sprite by loading all animation frames from a single image (a sprite sheet)
 //! into a texture atlas, and changing the displayed image periodically.
 
-use std::{io::Read, str::Bytes};
-
 use bevy::{
-    asset::{io::Reader, meta::Settings, AssetLoader, AsyncReadExt},
-    math::{vec2, vec3, quat},
+    math::{vec3, quat},
     prelude::*,
 };
-use enemy::{EnemyStates, Enemy};
-use player::PlayerStates;
-use ron::{error::SpannedError, Map};
-use serde::{
-    de::{self, Visitor},
-    Deserialize, Deserializer, Serialize,
-};
+use combat::CombatPlugin;
+use effects::OneShot;
+use fighter_def::{FighterDef, FighterDefLoader, FighterRole, FighterSource};
+use hud::HeartText;
+use impact::CameraShake;
+use loading::GameAssets;
+use replay::{Playback, Recorder, Replay};
+use rng::CombatRng;
+use roster::{Progress, Roster, RosterLoader};
+use round::Round;
+use save::{SavePath, Settings};
+use state::{AppState, FightEntity, ScreenEntity};
+use timestep::SimTranslation;
+use training::{FrameData, Training};
 use util::*;
 
 mod player;
 mod enemy;
+mod attack;
+mod audio;
+mod combat;
+mod controls;
+#[cfg(feature = "debug_overlay")]
+mod debug;
+mod effects;
+mod fighter;
+mod fighter_def;
+mod hud;
+mod impact;
+mod knockdown;
+mod loading;
+mod password;
+mod pattern;
+mod pause;
+mod replay;
+mod rng;
+mod roster;
+mod round;
+mod save;
+mod score;
+#[cfg(test)]
+mod sim;
+mod state;
+mod timestep;
+mod training;
 mod util;
 
-#[derive(Component)]
-struct HeartText;
+fn main() {
+    let mut app = App::new();
 
-#[derive(Resource)]
-struct Entities {
-    player: Entity,
-    enemy: Enemy,
-    heartText: HeartText
-}
+    let rng = CombatRng::from_seed_or_random(seed_arg());
+    if let Some(path) = arg_value("--record") {
+        app.insert_resource(Recorder {
+            path: Some(path.into()),
+            replay: Replay::new(rng.seed()),
+        });
+    }
+    #[cfg(feature = "debug_overlay")]
+    app.add_plugins(debug::DebugOverlayPlugin);
 
-fn main() {
-    App::new()
+    app
         .add_plugins(DefaultPlugins.set(ImagePlugin::default_nearest())) // prevents blurry sprites
-        .add_systems(Startup, setup)
+        .add_plugins(CombatPlugin)
+        .insert_resource(rng)
+        .init_resource::<Progress>()
+        .init_resource::<Settings>()
+        .init_asset::<FighterDef>()
+        .init_asset_loader::<FighterDefLoader>()
+        .init_asset::<Roster>()
+        .init_asset_loader::<RosterLoader>()
+        .add_systems(PreStartup, ((load_replay_arg, apply_deferred, save::load_saved_game).chain(), controls::load_bindings_file))
+        .add_systems(Startup, (spawn_camera, log_seed, apply_round_args))
+        .add_systems(OnEnter(AppState::Loading), (loading::start_loading, audio::load_sounds, effects::load_effects))
+        .add_systems(Update, loading::check_loading.run_if(in_state(AppState::Loading)))
+        .add_systems(OnExit(AppState::Loading), state::despawn_all::<ScreenEntity>)
+        .add_systems(OnEnter(AppState::Title), (state::spawn_title, training::end_training))
+        .add_systems(OnExit(AppState::Title), state::despawn_all::<ScreenEntity>)
+        .add_systems(Update, (password::open_password_entry, training::open_training).run_if(in_state(AppState::Title)))
+        .add_systems(OnEnter(AppState::Password), password::spawn_password_entry)
+        .add_systems(Update, (password::enter_password, password::update_password_text).chain().run_if(in_state(AppState::Password)))
+        .add_systems(OnExit(AppState::Password), state::despawn_all::<ScreenEntity>)
+        .add_systems(OnEnter(AppState::Circuit), roster::spawn_circuit)
+        .add_systems(OnExit(AppState::Circuit), state::despawn_all::<ScreenEntity>)
+        .add_systems(OnEnter(AppState::PreFight), (spawn_fight, state::spawn_pre_fight, audio::start_music).after(replay::set_up_replayed_fight))
+        .add_systems(OnEnter(AppState::PreFight), training::spawn_frame_data.run_if(resource_exists::<Training>()).after(replay::set_up_replayed_fight))
+        .add_systems(Update, training::update_frame_data_text.run_if(resource_exists::<Training>().and_then(resource_exists::<FrameData>())))
+        .add_systems(Update, state::count_down_pre_fight.run_if(in_state(AppState::PreFight)))
+        .add_systems(OnExit(AppState::PreFight), (state::despawn_all::<ScreenEntity>, audio::announce_fight))
+        .add_systems(Update, state::end_fight.run_if(in_state(AppState::Fighting)))
+        .add_systems(Update, state::toggle_pause.run_if(in_state(AppState::Fighting).or_else(in_state(AppState::Paused))))
+        .add_systems(OnEnter(AppState::Paused), (pause::spawn_pause_menu, audio::pause_music))
+        .add_systems(Update, (pause::navigate_pause_menu, pause::update_pause_text).chain().run_if(in_state(AppState::Paused)))
+        .add_systems(OnExit(AppState::Paused), (state::despawn_all::<ScreenEntity>, audio::resume_music))
+        .add_systems(OnEnter(AppState::Victory), (roster::record_fight, state::spawn_result).chain())
+        .add_systems(OnEnter(AppState::Defeat), (roster::record_fight, state::spawn_result).chain())
+        .add_systems(OnExit(AppState::Victory), (state::despawn_all::<ScreenEntity>, state::despawn_all::<FightEntity>))
+        .add_systems(OnExit(AppState::Defeat), (state::despawn_all::<ScreenEntity>, state::despawn_all::<FightEntity>))
+        .add_systems(Update, state::press_enter)
+        .add_systems(Update, fighter_def::apply_fighter_defs)
+        .add_systems(
+            Update,
+            save::write_save.run_if(
+                resource_exists::<SavePath>().and_then(resource_changed::<Progress>().or_else(resource_changed::<Settings>())),
+            ),
+        )
         .add_systems(Update, animate_sprite)
-        .add_systems(Update, player::update_player_movement)
-        .add_systems(Update, enemy::update_enemy_movement)
+        .add_systems(Update, effects::spawn_combat_effects.run_if(resource_exists::<effects::Effects>()))
+        .add_systems(Update, (audio::load_voices, audio::play_combat_sounds, audio::announce_result).chain().run_if(resource_exists::<audio::Sounds>()))
+        .add_systems(Update, audio::update_music_volume.run_if(resource_changed::<Settings>()))
+        .add_systems(Update, impact::apply_impact_settings.run_if(resource_changed::<Settings>()))
+        .add_systems(Update, impact::shake_camera)
+        .add_systems(Update, (hud::update_heart_text, hud::update_score_text, hud::update_health_bars, hud::update_round_hud, hud::update_count_text))
         .run();
 }
 
+/// Returns the value passed as `<name> <value>` or `<name>=<value>`.
+fn arg_value(name: &str) -> Option<String> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.strip_prefix(name) {
+            Some("") => return args.next(),
+            Some(value) if value.starts_with('=') => return Some(value[1..].to_string()),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Reads the combat seed from `--seed`, falling back to the `CRUCIBLE_SEED`
+/// environment variable.
+fn seed_arg() -> Option<u64> {
+    arg_value("--seed")
+        .or_else(|| std::env::var("CRUCIBLE_SEED").ok())?
+        .parse()
+        .ok()
+}
+
+/// Sets up rounds from `--rounds <count>` and `--round-length <seconds>`,
+/// defaulting to the saved settings.
+fn apply_round_args(settings: Res<Settings>, mut round: ResMut<Round>) {
+    let count = arg_value("--rounds").and_then(|count| count.parse().ok());
+    let length = arg_value("--round-length").and_then(|length| length.parse().ok());
+    *round = Round::new(count.unwrap_or(settings.rounds), length.unwrap_or(settings.round_length));
+}
+
+/// Starts playing back `--replay <file>`, which brings its own seed. Runs
+/// once logging is up so a bad file is reported before the game gives up.
+fn load_replay_arg(mut commands: Commands, mut rng: ResMut<CombatRng>, recorder: Option<ResMut<Recorder>>) {
+    let Some(path) = arg_value("--replay") else {
+        return;
+    };
+    match replay::load_replay(&path) {
+        Ok(replay) => {
+            *rng = CombatRng::new(replay.seed);
+            if let Some(mut recorder) = recorder {
+                recorder.replay.seed = replay.seed;
+            }
+            commands.insert_resource(Playback::new(replay));
+        }
+        Err(err) => {
+            error!("{path}: {err}");
+            std::process::exit(1);
+        }
+    }
+}
+
+fn log_seed(rng: Res<CombatRng>) {
+    info!("combat seed: {}", rng.seed());
+}
+
+/// Loops every clip except [`OneShot`]s, which are despawned at the end.
 fn animate_sprite(
+    mut commands: Commands,
     time: Res<Time>,
     mut query: Query<(
+        Entity,
         &AnimationIndices,
         &mut AnimationTimer,
         &mut TextureAtlasSprite,
+        Has<OneShot>,
     )>,
 ) {
-    for (indices, mut timer, mut sprite) in &mut query {
+    for (entity, indices, mut timer, mut sprite, one_shot) in &mut query {
         timer.tick(time.delta());
         if timer.just_finished() {
             sprite.index = if sprite.index == indices.last {
+                if one_shot {
+                    commands.entity(entity).despawn_recursive();
+                }
                 indices.first
             } else {
                 sprite.index + 1
@@ -61,144 +202,86 @@ fn animate_sprite(
     }
 }
 
-fn setup(
+fn spawn_camera(mut commands: Commands) {
+    commands.spawn((Camera2dBundle::default(), CameraShake::default()));
+}
+
+fn spawn_fight(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
-    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    assets: Res<GameAssets>,
+    rosters: Res<Assets<Roster>>,
+    progress: Res<Progress>,
 ) {
-    let betty_handle = asset_server.load("sprites/atlases/betty_mercy.png");
-    let mut betty_atlas = TextureAtlas::new_empty(betty_handle, vec2(481.0, 178.0));
-    let abigail_handle = asset_server.load("sprites/atlases/abigail.png");
-    let mut abigail_atlas = TextureAtlas::new_empty(abigail_handle, vec2(1118.0, 211.0));
-
-    //Betty
-
-    betty_atlas.add_texture(AtlasUtil::from_corner_size(1., 1., 24., 88.)); // Idle
-    betty_atlas.add_texture(AtlasUtil::from_corner_size(26., 1., 24., 88.));
-
-    betty_atlas.add_texture(AtlasUtil::from_corner_size(126., 1., 32., 88.)); // Punch
-    betty_atlas.add_texture(AtlasUtil::from_corner_size(159., 1., 32., 88.));
-    betty_atlas.add_texture(AtlasUtil::from_corner_size(192., 1., 24., 88.));
-    betty_atlas.add_texture(AtlasUtil::from_corner_size(217., 1., 24., 88.));
-
-    betty_atlas.add_texture(AtlasUtil::from_corner_size(151., 90., 32., 88.)); // Hit
-    betty_atlas.add_texture(AtlasUtil::from_corner_size(184., 90., 32., 88.));
-
-    betty_atlas.add_texture(AtlasUtil::from_corner_size(349., 90., 24., 88.)); // Block
-    betty_atlas.add_texture(AtlasUtil::from_corner_size(374., 90., 24., 88.));
-
-    betty_atlas.add_texture(AtlasUtil::from_corner_size(51., 90., 24., 88.)); // Dodge
-    betty_atlas.add_texture(AtlasUtil::from_corner_size(76., 90., 24., 88.));
-
-    betty_atlas.add_texture(AtlasUtil::from_corner_size(217., 90., 32., 88.)); // Death
-
-    //Abigail
-
-    abigail_atlas.add_texture(AtlasUtil::from_corner_size(1., 1., 40., 104.)); // Idle
-    abigail_atlas.add_texture(AtlasUtil::from_corner_size(42., 1., 40., 104.)); 
-
-    abigail_atlas.add_texture(AtlasUtil::from_corner_size(329., 1., 32., 104.)); // Punch
-    abigail_atlas.add_texture(AtlasUtil::from_corner_size(370., 1., 32., 104.));
-    abigail_atlas.add_texture(AtlasUtil::from_corner_size(1., 106., 40., 104.));
-
-    abigail_atlas.add_texture(AtlasUtil::from_corner_size(272., 106., 40., 104.)); // Hit
-
-    abigail_atlas.add_texture(AtlasUtil::from_corner_size(91., 106., 32., 104.)); // Block
-
-    abigail_atlas.add_texture(AtlasUtil::from_corner_size(418., 106., 54., 104.)); // Death
-
-
-
-    let betty_atlas_handle = texture_atlases.add(betty_atlas);
-    let abigail_atlas_handle = texture_atlases.add(abigail_atlas);
-    // Use only the subset of sprites in the sheet that make up the run animation
-    let animation_indices = AnimationIndices { first: 0, last: 1 };
-    let betty_sprite = TextureAtlasSprite::new(animation_indices.first);
-    let abigail_sprite = TextureAtlasSprite::new(0);
-    commands.spawn(Camera2dBundle::default());
+    let Some(roster) = rosters.get(&assets.roster) else {
+        return;
+    };
     commands.spawn((
         SpriteSheetBundle {
-            texture_atlas: betty_atlas_handle,
-            sprite: betty_sprite,
             transform: Transform::from_scale(Vec3::splat(2.0)),
             ..default()
         },
-        player::Player::new(
-            Vec3 {
+        FighterSource {
+            def: assets.player.clone(),
+            role: FighterRole::Player,
+            origin: Vec3 {
                 x: 0.0,
                 y: -80.0,
                 z: 1.0,
             },
-            Animator::new(
-                AnimationTimer(Timer::from_seconds(0.25, TimerMode::Repeating)),
-                AnimationIndices { first: 0, last: 1 },
-                true,
-            ),
-            PlayerStates {
-                idle: AnimationIndices { first: 0, last: 1 },
-                punch: AnimationIndices { first: 2, last: 5 },
-                hit: AnimationIndices { first: 6, last: 7 },
-                block: AnimationIndices { first: 8, last: 9 },
-                dodge: AnimationIndices { first: 10, last: 11 },
-                death: AnimationIndices { first: 12, last: 12 }
-            },
-        )
+        },
+        SimTranslation::new(vec3(0.0, -80.0, 1.0)),
+        FightEntity,
     ));
     commands.spawn((
         SpriteBundle {
-            texture: asset_server.load("sprites/stage.png"),
+            texture: assets.stage.clone(),
             transform: Transform { translation: vec3(0.0, 0.0, -1.0), rotation: quat(0.0, 0.0, 0.0, 1.0), scale: Vec3::splat(2.0) },
             ..default()
-        }
+        },
+        FightEntity,
     ));
     commands.spawn((
         SpriteSheetBundle {
-            texture_atlas: abigail_atlas_handle,
-            sprite: abigail_sprite,
             transform: Transform { translation: vec3(0.0, 0.0, 0.0), rotation: quat(0.0, 0.0, 0.0, 1.0), scale: Vec3::splat(2.0) },
             ..default()
         },
-        enemy::Enemy::new(
-            Vec3 {
+        FighterSource {
+            def: roster.challenger(&progress).clone(),
+            role: FighterRole::Enemy,
+            origin: Vec3 {
                 x: 0.0,
                 y: 0.0,
                 z: 0.0,
             },
-            Animator::new(
-                AnimationTimer(Timer::from_seconds(0.25, TimerMode::Repeating)),
-                AnimationIndices { first: 0, last: 1 },
-                true,
-            ),
-            EnemyStates {
-                idle: AnimationIndices { first: 0, last: 1 },
-                punch_warning: AnimationIndices { first: 2, last: 3},
-                punch: AnimationIndices { first: 4, last: 4 },
-                hit: AnimationIndices { first: 5, last: 5 },
-                block: AnimationIndices { first: 6, last: 6 },
-                death: AnimationIndices { first: 7, last: 7 }
-            },
-        )
+        },
+        SimTranslation::new(vec3(0.0, 0.0, 0.0)),
+        FightEntity,
     ));
     commands.spawn((
         Text2dBundle {
             text: Text::from_section(
                 "00",
-            TextStyle { font: asset_server.load("fonts/font.ttf"), font_size: 16.0, color: Color::hex("FC9838").expect("wtf") }
+            TextStyle { font: assets.font.clone(), font_size: 16.0, color: Color::hex("FC9838").expect("wtf") }
             ).with_alignment(TextAlignment::Right),
             transform: Transform::from_translation(Vec3 { x: -128.0, y: 182.0, z: 0.0 }),
             ..default()
         },
-        HeartText {}
+        HeartText {},
+        FightEntity,
     ));
     commands.spawn((
         Text2dBundle {
             text: Text::from_section(
                 "00",
-            TextStyle { font: asset_server.load("fonts/font.ttf"), font_size: 16.0, color: Color::hex("D82800").expect("wtf") }
+            TextStyle { font: assets.font.clone(), font_size: 16.0, color: Color::hex("D82800").expect("wtf") }
             ).with_alignment(TextAlignment::Right),
             transform: Transform::from_translation(Vec3 { x: -126.0, y: 182.0, z: -0.1 }),
             ..default()
         },
-        HeartText {}
+        HeartText {},
+        FightEntity,
     ));
+    hud::spawn_health_bars(&mut commands);
+    hud::spawn_round_hud(&mut commands, &asset_server);
 }
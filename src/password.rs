@@ -0,0 +1,249 @@
+//! Ten-digit passwords that carry a career from one session to the next
+//! without a save file. A password holds how far through the roster the
+//! player is and their career wins, losses and KOs, guarded by a checksum
+//! and scrambled so neighbouring careers don't get neighbouring codes.
+//!
+//! Records against individual opponents don't fit; a career restored from a
+//! password keeps its totals in [`Progress::carried_over`].
+
+use std::fmt;
+
+use bevy::prelude::*;
+
+use crate::{
+    controls::{Action, ActionState},
+    loading::GameAssets,
+    roster::{Progress, Record, Roster},
+    state::{self, AppState},
+};
+
+const DIGITS: usize = 10;
+const BEATEN_BITS: u32 = 5;
+/// Bits for each of wins, losses and KOs; higher counts are capped.
+const COUNT_BITS: u32 = 7;
+const PAYLOAD_BITS: u32 = BEATEN_BITS + 3 * COUNT_BITS;
+const CHECKSUM_BITS: u32 = 7;
+const CODE_BITS: u32 = PAYLOAD_BITS + CHECKSUM_BITS;
+const CODE_MASK: u64 = (1 << CODE_BITS) - 1;
+/// Multiplying by this scrambles the code; `UNSCRAMBLE` is its inverse
+/// modulo 2^`CODE_BITS`, so both can wrap on overflow.
+const SCRAMBLE: u64 = 0x1_2F6D_9A35;
+const UNSCRAMBLE: u64 = 0x1_7030_681D;
+
+#[derive(Debug, PartialEq)]
+pub enum PasswordError {
+    Length(usize),
+    NotDigit(char),
+    Checksum,
+    /// Further through the roster than there are opponents.
+    Beaten(usize),
+}
+
+impl fmt::Display for PasswordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PasswordError::Length(len) => write!(f, "password has {len} digits, not {DIGITS}"),
+            PasswordError::NotDigit(c) => write!(f, "`{c}` is not a digit"),
+            PasswordError::Checksum => write!(f, "password checksum does not match"),
+            PasswordError::Beaten(beaten) => write!(f, "password is past the end of the roster ({beaten} beaten)"),
+        }
+    }
+}
+
+impl std::error::Error for PasswordError {}
+
+fn checksum(payload: u64) -> u64 {
+    (0..PAYLOAD_BITS)
+        .step_by(4)
+        .fold(0, |sum, shift| (sum * 31 + ((payload >> shift) & 0xF) + 1) % 127)
+}
+
+/// The password for `progress`, as `123 456 7890`.
+pub fn encode(progress: &Progress) -> String {
+    let career = progress.career();
+    let cap = |count: u64, bits: u32| count.min((1 << bits) - 1);
+    let payload = cap(progress.beaten as u64, BEATEN_BITS)
+        | cap(career.wins as u64, COUNT_BITS) << BEATEN_BITS
+        | cap(career.losses as u64, COUNT_BITS) << (BEATEN_BITS + COUNT_BITS)
+        | cap(career.kos as u64, COUNT_BITS) << (BEATEN_BITS + 2 * COUNT_BITS);
+    let code = (payload | checksum(payload) << PAYLOAD_BITS).wrapping_mul(SCRAMBLE) & CODE_MASK;
+    let digits = format!("{code:0DIGITS$}");
+    format!("{} {} {}", &digits[..3], &digits[3..6], &digits[6..])
+}
+
+/// Reads a password back into a career of `opponents` opponents. Spaces are
+/// ignored.
+pub fn decode(password: &str, opponents: usize) -> Result<Progress, PasswordError> {
+    let digits: String = password.chars().filter(|c| !c.is_whitespace()).collect();
+    if let Some(c) = digits.chars().find(|c| !c.is_ascii_digit()) {
+        return Err(PasswordError::NotDigit(c));
+    }
+    if digits.len() != DIGITS {
+        return Err(PasswordError::Length(digits.len()));
+    }
+    let code: u64 = digits.parse().expect("ten digits fit in a u64");
+    if code > CODE_MASK {
+        return Err(PasswordError::Checksum);
+    }
+    let code = code.wrapping_mul(UNSCRAMBLE) & CODE_MASK;
+    let payload = code & ((1 << PAYLOAD_BITS) - 1);
+    if code >> PAYLOAD_BITS != checksum(payload) {
+        return Err(PasswordError::Checksum);
+    }
+
+    let field = |shift: u32, bits: u32| ((payload >> shift) & ((1 << bits) - 1)) as u32;
+    let beaten = field(0, BEATEN_BITS) as usize;
+    if beaten > opponents {
+        return Err(PasswordError::Beaten(beaten));
+    }
+    Ok(Progress {
+        beaten,
+        records: Default::default(),
+        carried_over: Record {
+            wins: field(BEATEN_BITS, COUNT_BITS),
+            losses: field(BEATEN_BITS + COUNT_BITS, COUNT_BITS),
+            kos: field(BEATEN_BITS + 2 * COUNT_BITS, COUNT_BITS),
+            best_ko: None,
+        },
+    })
+}
+
+/// Digits typed so far on the password screen.
+#[derive(Resource, Default)]
+pub struct PasswordEntry {
+    pub digits: String,
+    /// The digit being picked for the next place without a keyboard.
+    pub picking: Option<u8>,
+    pub error: Option<PasswordError>,
+}
+
+#[derive(Component)]
+pub struct PasswordText;
+
+const DIGIT_KEYS: [(KeyCode, KeyCode); 10] = [
+    (KeyCode::Key0, KeyCode::Numpad0),
+    (KeyCode::Key1, KeyCode::Numpad1),
+    (KeyCode::Key2, KeyCode::Numpad2),
+    (KeyCode::Key3, KeyCode::Numpad3),
+    (KeyCode::Key4, KeyCode::Numpad4),
+    (KeyCode::Key5, KeyCode::Numpad5),
+    (KeyCode::Key6, KeyCode::Numpad6),
+    (KeyCode::Key7, KeyCode::Numpad7),
+    (KeyCode::Key8, KeyCode::Numpad8),
+    (KeyCode::Key9, KeyCode::Numpad9),
+];
+
+/// Opens the password screen from the title.
+pub fn open_password_entry(actions: Res<ActionState>, mut next_state: ResMut<NextState<AppState>>) {
+    if actions.just_pressed(Action::Password) {
+        next_state.set(AppState::Password);
+    }
+}
+
+pub fn spawn_password_entry(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(PasswordEntry::default());
+    let screen = state::spawn_screen(&mut commands, &asset_server, String::new());
+    commands.entity(screen).insert(PasswordText);
+}
+
+/// Digits are typed, or picked with up and down and entered with right. Erase
+/// takes one back, Confirm checks the password and Back returns to the title.
+pub fn enter_password(
+    keys: Res<Input<KeyCode>>,
+    actions: Res<ActionState>,
+    assets: Res<GameAssets>,
+    rosters: Res<Assets<Roster>>,
+    mut entry: ResMut<PasswordEntry>,
+    mut progress: ResMut<Progress>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if actions.just_pressed(Action::Back) {
+        next_state.set(AppState::Title);
+        return;
+    }
+    if actions.just_pressed(Action::Erase) {
+        // a digit still being picked goes first
+        if entry.picking.take().is_none() {
+            entry.digits.pop();
+        }
+        entry.error = None;
+    }
+    if entry.digits.len() < DIGITS {
+        if actions.just_pressed(Action::MenuUp) {
+            entry.picking = Some(entry.picking.map_or(0, |digit| (digit + 1) % 10));
+        }
+        if actions.just_pressed(Action::MenuDown) {
+            entry.picking = Some(entry.picking.map_or(9, |digit| (digit + 9) % 10));
+        }
+    }
+    let mut typed: Vec<u8> = DIGIT_KEYS
+        .into_iter()
+        .enumerate()
+        .filter(|(_, (key, numpad))| keys.any_just_pressed([*key, *numpad]))
+        .map(|(digit, _)| digit as u8)
+        .collect();
+    if actions.just_pressed(Action::MenuRight) {
+        typed.extend(entry.picking);
+    }
+    for digit in typed {
+        if entry.digits.len() < DIGITS {
+            entry.digits.push(char::from(b'0' + digit));
+            entry.picking = None;
+            entry.error = None;
+        }
+    }
+    if !actions.just_pressed(Action::Confirm) {
+        return;
+    }
+    let opponents = rosters.get(&assets.roster).map_or(0, |roster| roster.opponents().count());
+    match decode(&entry.digits, opponents) {
+        Ok(restored) => {
+            *progress = restored;
+            next_state.set(AppState::Circuit);
+        }
+        Err(err) => {
+            info!("{err}");
+            entry.error = Some(err);
+        }
+    }
+}
+
+pub fn update_password_text(entry: Res<PasswordEntry>, mut text_query: Query<&mut Text, With<PasswordText>>) {
+    let picking = entry.picking.map(|digit| char::from(b'0' + digit));
+    let mut shown: String = entry.digits.chars().chain(picking).chain(std::iter::repeat('-')).take(DIGITS).collect();
+    shown.insert(6, ' ');
+    shown.insert(3, ' ');
+    let status = if entry.error.is_some() { "INVALID PASSWORD" } else { "" };
+    for mut text in &mut text_query {
+        text.sections[0].value = format!("ENTER PASSWORD\n\n{shown}\n\n{status}\n\n\nESC TO GO BACK");
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn password_restores_career_and_rejects_typos() {
+        let mut progress = Progress::default();
+        progress.record("a".to_string(), 0, true, Some(50.0));
+        progress.record("b".to_string(), 1, false, None);
+        progress.record("b".to_string(), 1, true, None);
+        let code = encode(&progress);
+
+        let restored = decode(&code, 4).unwrap();
+        assert_eq!(restored.beaten, 2);
+        let career = restored.career();
+        assert_eq!((career.wins, career.losses, career.kos), (2, 1, 1));
+        assert_eq!(encode(&restored), code);
+
+        let digits: Vec<char> = code.chars().filter(char::is_ascii_digit).collect();
+        let mut typo = digits.clone();
+        typo[4] = if typo[4] == '9' { '0' } else { char::from(typo[4] as u8 + 1) };
+        let typo: String = typo.into_iter().collect();
+        assert!(decode(&typo, 4).is_err());
+        assert_eq!(decode("12345", 4).unwrap_err(), PasswordError::Length(5));
+        assert_eq!(decode(&code, 1).unwrap_err(), PasswordError::Beaten(2));
+    }
+}
@@ -0,0 +1,98 @@
+//! Authored enemy attack patterns, read from the `pattern` list of a fighter
+//! definition and played back in a loop.
+//!
+//! ```ron
+//! pattern: [
+//!     Wait(1.5),
+//!     Feint(Left),
+//!     Punch(Right),
+//!     Block,
+//!     Choose([
+//!         (2, [Wait(1.0), Punch(Left)]),
+//!         (1, [Block]),
+//!     ]),
+//! ],
+//! ```
+
+use std::collections::VecDeque;
+
+use rand::{seq::SliceRandom, Rng};
+use serde::Deserialize;
+
+use crate::fighter::Side;
+
+#[derive(Deserialize, Clone, Debug)]
+pub enum PatternStep {
+    /// Stand idle for the given number of seconds.
+    Wait(f32),
+    Block,
+    /// Show the punch wind-up without throwing it.
+    Feint(Side),
+    Punch(Side),
+    /// Runs one of the weighted branches, re-rolled every time it is reached.
+    Choose(Vec<(u32, Vec<PatternStep>)>),
+}
+
+/// A single thing for the enemy to do next, with every `Choose` resolved.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum EnemyAction {
+    Wait(f32),
+    Block,
+    Feint(Side),
+    Punch(Side),
+}
+
+pub struct AttackPattern {
+    steps: Vec<PatternStep>,
+    queue: VecDeque<EnemyAction>,
+}
+
+impl AttackPattern {
+    /// An empty `steps` falls back to [`AttackPattern::default`].
+    pub fn new(steps: Vec<PatternStep>) -> AttackPattern {
+        if steps.is_empty() {
+            return AttackPattern::default();
+        }
+        AttackPattern {
+            steps,
+            queue: VecDeque::new(),
+        }
+    }
+
+    /// Returns the next action, starting the pattern over once it runs out.
+    pub fn next(&mut self, rng: &mut impl Rng) -> EnemyAction {
+        if self.queue.is_empty() {
+            expand(&self.steps, &mut self.queue, rng);
+        }
+        // a pattern made only of zero-weight branches expands to nothing
+        self.queue.pop_front().unwrap_or(EnemyAction::Wait(1.5))
+    }
+}
+
+impl Default for AttackPattern {
+    /// The original dice roll: half the time wait, otherwise block or punch.
+    fn default() -> Self {
+        AttackPattern::new(vec![PatternStep::Choose(vec![
+            (1, vec![PatternStep::Block]),
+            (1, vec![PatternStep::Punch(Side::Left)]),
+            (1, vec![PatternStep::Punch(Side::Right)]),
+            (3, vec![PatternStep::Wait(1.5)]),
+        ])])
+    }
+}
+
+fn expand(steps: &[PatternStep], queue: &mut VecDeque<EnemyAction>, rng: &mut impl Rng) {
+    for step in steps {
+        match step {
+            PatternStep::Wait(secs) => queue.push_back(EnemyAction::Wait(*secs)),
+            PatternStep::Block => queue.push_back(EnemyAction::Block),
+            PatternStep::Feint(side) => queue.push_back(EnemyAction::Feint(*side)),
+            PatternStep::Punch(side) => queue.push_back(EnemyAction::Punch(*side)),
+            PatternStep::Choose(branches) => {
+                if let Ok((_, branch)) = branches.choose_weighted(rng, |(weight, _)| *weight) {
+                    expand(branch, queue, rng);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,228 @@
+//! The pause menu. Pausing stops the virtual clock as well as leaving
+//! [`AppState::Fighting`], so fighter timers, animators and the round clock
+//! all freeze and nothing catches up on the time spent paused.
+
+use bevy::prelude::*;
+
+use crate::{
+    controls::{Action, ActionState},
+    save::Settings,
+    state::{AppState, FightEntity, ScreenEntity},
+    training::Training,
+};
+
+#[derive(Clone, Copy, PartialEq)]
+enum Item {
+    Resume,
+    /// Only in training.
+    EnemyAction,
+    Restart,
+    Settings,
+    Quit,
+}
+
+fn items(training: bool) -> Vec<Item> {
+    let mut items = vec![Item::Resume, Item::Restart, Item::Settings, Item::Quit];
+    if training {
+        items.insert(1, Item::EnemyAction);
+    }
+    items
+}
+
+const SETTINGS_ITEMS: [&str; 6] = ["MASTER VOLUME", "MUSIC VOLUME", "SFX VOLUME", "HIT STOP", "SCREEN SHAKE", "BACK"];
+/// How much one press left or right changes a volume or shake level.
+const LEVEL_STEP: f32 = 0.1;
+const MAX_HIT_STOP_FRAMES: u32 = 8;
+
+#[derive(Resource, Default)]
+pub struct PauseMenu {
+    selected: usize,
+    /// Showing the settings page instead of the main one.
+    in_settings: bool,
+}
+
+#[derive(Component)]
+pub struct PauseText;
+
+pub fn freeze_time(mut time: ResMut<Time<Virtual>>) {
+    time.pause();
+}
+
+pub fn unfreeze_time(mut time: ResMut<Time<Virtual>>) {
+    time.unpause();
+}
+
+pub fn spawn_pause_menu(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(PauseMenu::default());
+    commands.spawn((
+        SpriteBundle {
+            sprite: Sprite {
+                color: Color::rgba(0.0, 0.0, 0.0, 0.75),
+                custom_size: Some(Vec2::new(512.0, 448.0)),
+                ..default()
+            },
+            transform: Transform::from_translation(Vec3::new(0.0, 0.0, 9.0)),
+            ..default()
+        },
+        ScreenEntity,
+    ));
+    commands.spawn((
+        Text2dBundle {
+            text: Text::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/font.ttf"),
+                    font_size: 16.0,
+                    color: Color::WHITE,
+                },
+            )
+            .with_alignment(TextAlignment::Center),
+            transform: Transform::from_translation(Vec3::new(0.0, 0.0, 10.0)),
+            ..default()
+        },
+        PauseText,
+        ScreenEntity,
+    ));
+}
+
+/// Up and down to pick, confirm to choose; left and right change a setting.
+/// Settings changed here are saved like any others.
+#[allow(clippy::too_many_arguments)]
+pub fn navigate_pause_menu(
+    mut commands: Commands,
+    actions: Res<ActionState>,
+    mut menu: ResMut<PauseMenu>,
+    mut settings: ResMut<Settings>,
+    training: Option<ResMut<Training>>,
+    mut next_state: ResMut<NextState<AppState>>,
+    fight_query: Query<Entity, With<FightEntity>>,
+) {
+    // Start is both pause and confirm; pressing it here resumes
+    if actions.just_pressed(Action::Pause) {
+        return;
+    }
+    let items = items(training.is_some());
+    let count = if menu.in_settings { SETTINGS_ITEMS.len() } else { items.len() };
+    if actions.just_pressed(Action::MenuUp) {
+        menu.selected = (menu.selected + count - 1) % count;
+    }
+    if actions.just_pressed(Action::MenuDown) {
+        menu.selected = (menu.selected + 1) % count;
+    }
+    let change = match (actions.just_pressed(Action::MenuLeft), actions.just_pressed(Action::MenuRight)) {
+        (true, false) => -1,
+        (false, true) => 1,
+        _ => 0,
+    };
+
+    if menu.in_settings {
+        if menu.selected == SETTINGS_ITEMS.len() - 1 {
+            if actions.just_pressed(Action::Confirm) {
+                *menu = PauseMenu {
+                    selected: items.iter().position(|item| *item == Item::Settings).unwrap_or(0),
+                    in_settings: false,
+                };
+            }
+            return;
+        }
+        // only touch the settings on a change, or they'd be saved every frame
+        if change == 0 {
+            return;
+        }
+        let level = match menu.selected {
+            0 => &mut settings.master_volume,
+            1 => &mut settings.music_volume,
+            2 => &mut settings.sfx_volume,
+            3 => {
+                settings.hit_stop_frames = settings.hit_stop_frames.saturating_add_signed(change).min(MAX_HIT_STOP_FRAMES);
+                return;
+            }
+            _ => &mut settings.screen_shake,
+        };
+        // rounded so repeated steps land back on whole tenths
+        *level = ((*level + change as f32 * LEVEL_STEP).clamp(0., 1.) * 10.).round() / 10.;
+        return;
+    }
+
+    let item = items[menu.selected];
+    if item == Item::EnemyAction {
+        if let Some(mut training) = training {
+            if change != 0 || actions.just_pressed(Action::Confirm) {
+                training.action = training.action.cycle(change >= 0);
+            }
+        }
+        return;
+    }
+    if !actions.just_pressed(Action::Confirm) {
+        return;
+    }
+    match item {
+        Item::Resume => next_state.set(AppState::Fighting),
+        Item::Settings => {
+            *menu = PauseMenu {
+                selected: 0,
+                in_settings: true,
+            }
+        }
+        // restarting or quitting both throw the current fight away
+        Item::Restart | Item::Quit => {
+            for entity in &fight_query {
+                commands.entity(entity).despawn_recursive();
+            }
+            next_state.set(if item == Item::Restart { AppState::PreFight } else { AppState::Title });
+        }
+        Item::EnemyAction => {}
+    }
+}
+
+pub fn update_pause_text(
+    menu: Res<PauseMenu>,
+    settings: Res<Settings>,
+    training: Option<Res<Training>>,
+    mut text_query: Query<&mut Text, With<PauseText>>,
+) {
+    let lines: Vec<String> = if menu.in_settings {
+        let percent = |level: f32| format!("{:>3}%", (level * 100.).round() as u32);
+        let values = [
+            percent(settings.master_volume),
+            percent(settings.music_volume),
+            percent(settings.sfx_volume),
+            match settings.hit_stop_frames {
+                0 => "OFF".to_string(),
+                frames => format!("{frames} FRAMES"),
+            },
+            percent(settings.screen_shake),
+        ];
+        SETTINGS_ITEMS
+            .iter()
+            .enumerate()
+            .map(|(index, item)| match values.get(index) {
+                Some(value) => format!("{item}  {value}"),
+                None => item.to_string(),
+            })
+            .collect()
+    } else {
+        items(training.is_some())
+            .into_iter()
+            .map(|item| match item {
+                Item::Resume => "RESUME".to_string(),
+                Item::EnemyAction => {
+                    let action = training.as_ref().map(|training| training.action.label()).unwrap_or_default();
+                    format!("ENEMY: {action}")
+                }
+                Item::Restart => "RESTART FIGHT".to_string(),
+                Item::Settings => "SETTINGS".to_string(),
+                Item::Quit => "QUIT TO TITLE".to_string(),
+            })
+            .collect()
+    };
+    let lines: Vec<String> = lines
+        .into_iter()
+        .enumerate()
+        .map(|(index, line)| if index == menu.selected { format!("> {line} <") } else { line })
+        .collect();
+    let title = if menu.in_settings { "SETTINGS" } else { "PAUSED" };
+    for mut text in &mut text_query {
+        text.sections[0].value = format!("{title}\n\n\n{}", lines.join("\n\n"));
+    }
+}
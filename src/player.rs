@@ -1,226 +1,228 @@
 use bevy::{
     ecs::{
         component::Component,
+        entity::Entity,
+        event::{EventReader, EventWriter},
         system::{Query, Res},
     },
-    input::{keyboard::KeyCode, Input},
-    math::{vec2, Vec3},
-    prelude::default,
+    math::Vec3,
+    render::color::Color,
     time::Time,
-    transform::components::Transform, sprite::{SpriteSheetBundle, TextureAtlas, TextureAtlasSprite},
+    sprite::TextureAtlasSprite,
 };
 
-use crate::{util::*, AnimationIndices, AnimationTimer, enemy::{Enemy, self}};
-
-#[derive(Default)]
-enum ActionDirection {
-    #[default]
-    None,
-    Left,
-    Right,
-}
+use crate::{util::*, attack::{AttackEvent, BlockedEvent, Defense, DodgedEvent, HitEvent}, fighter::{Fighter, FighterState, Side, StateClips}, fighter_def::{FighterClips, FighterDefError}, controls::PlayerInput, timestep::SimTranslation};
 
 #[derive(Component)]
 pub struct Player {
-    dodge_timer: f32,
-    action_dir: ActionDirection,
-    pub punch_timer: f32,
-    states: PlayerStates,
-    blocking: bool,
-    origin: Vec3,
-    animator: Animator,
-    is_hit_timer: f32,
-    hits_taken_total: i32,
-    heart : u32,
-    health : u32
+    pub fighter: Fighter<PlayerStates>,
+    /// Stamina: lost when the player is hit or has a punch blocked, and at
+    /// zero the player can't punch.
+    pub hearts: u32,
 }
 
 pub struct PlayerStates {
-    pub idle: AnimationIndices,
-    pub punch: AnimationIndices,
-    pub hit: AnimationIndices,
-    pub block: AnimationIndices,
-    pub dodge: AnimationIndices,
-    pub death: AnimationIndices
+    pub idle: SpriteClip,
+    pub punch: SpriteClip,
+    pub hit: SpriteClip,
+    pub block: SpriteClip,
+    pub dodge: SpriteClip,
+    pub death: SpriteClip
+}
+
+impl PlayerStates {
+    pub fn from_clips(clips: &FighterClips) -> Result<PlayerStates, FighterDefError> {
+        Ok(PlayerStates {
+            idle: clips.get("idle")?,
+            punch: clips.get("punch")?,
+            hit: clips.get("hit")?,
+            block: clips.get("block")?,
+            dodge: clips.get("dodge")?,
+            death: clips.get("death")?,
+        })
+    }
+}
+
+impl StateClips for PlayerStates {
+    fn clip(&self, state: FighterState) -> SpriteClip {
+        match state {
+            FighterState::Idle => self.idle,
+            FighterState::Blocking => self.block,
+            FighterState::Dodging(_) => self.dodge,
+            FighterState::WindUp(_) | FighterState::Punching(_) => self.punch,
+            FighterState::Hit => self.hit,
+            FighterState::Down | FighterState::KO => self.death,
+        }
+    }
 }
 
 impl Player {
-    pub fn new(origin: Vec3, animator: Animator, states: PlayerStates) -> Player {
+    pub fn new(origin: Vec3, states: PlayerStates) -> Player {
         Player {
-            states: states,
-            action_dir: ActionDirection::None,
-            punch_timer: 0.0,
-            dodge_timer: 0.0,
-            is_hit_timer: 0.0,
-            blocking: false,
-            origin: origin,
-            animator: animator,
-            hits_taken_total: 0,
-            heart: 20,
-            health: 100,
+            fighter: Fighter::new(origin, states, MAX_HEALTH),
+            hearts: MAX_HEARTS,
         }
     }
+
+    pub fn is_tired(&self) -> bool {
+        self.hearts == 0
+    }
+
+    /// Gives back a share of the missing health and hearts.
+    pub fn recover(&mut self, health_share: f32, heart_share: f32) {
+        self.fighter.recover(health_share);
+        let missing = MAX_HEARTS - self.hearts;
+        self.gain_hearts((missing as f32 * heart_share).round() as u32);
+    }
+
+    fn gain_hearts(&mut self, hearts: u32) {
+        self.hearts = (self.hearts + hearts).min(MAX_HEARTS);
+    }
+
+    fn lose_hearts(&mut self, hearts: u32) {
+        self.hearts = self.hearts.saturating_sub(hearts);
+    }
+
+    pub fn defense(&self) -> Defense {
+        self.fighter.defense()
+    }
+
+    pub fn take_hit(&mut self, damage: u32) {
+        self.fighter.take_hit(IS_HIT_TIMER, damage);
+    }
 }
 
 const DODGE_DISTANCE: f32 = 75.0;
 const DODGE_DURATION: f32 = 0.75;
-pub const PUNCH_DURATION: f32 = 0.5;
+const PUNCH_DURATION: f32 = 0.5;
+/// Only the first quarter of a punch can connect.
+const PUNCH_WINDOW: f32 = PUNCH_DURATION / 4.;
+const PUNCH_DAMAGE: u32 = 6;
 const IS_HIT_TIMER: f32 = 0.5;
+const MAX_HEALTH: u32 = 96;
+pub const MAX_HEARTS: u32 = 20;
+const HEARTS_LOST_WHEN_HIT: u32 = 3;
+const HEARTS_LOST_WHEN_BLOCKED: u32 = 1;
+const HEARTS_GAINED_ON_HIT: u32 = 1;
+/// Slipping a punch while tired gets some wind back.
+const HEARTS_GAINED_ON_TIRED_DODGE: u32 = 3;
+const TIRED_COLOR: Color = Color::rgb(1.0, 0.6, 0.8);
 
 pub fn update_player_movement(
     time: Res<Time>,
-    input: Res<Input<KeyCode>>,
-    mut player_query: Query<(&mut Player, &mut Transform, &mut TextureAtlasSprite)>,
-    enemy_query: Query<(&mut Enemy)>
+    input: Res<PlayerInput>,
+    mut player_query: Query<(Entity, &mut Player, &mut SimTranslation, &mut TextureAtlasSprite)>,
+    mut attacks: EventWriter<AttackEvent>,
 ) {
-    'player_loop: for (mut player, mut transform, mut sprite) in &mut player_query {
-        for enemy in &enemy_query {
-            if player.hits_taken_total > 5 {
-                let state = player.states.death;
-                player.animator.set_indices(state);
-                player.animator.set_frametime(0.1);
-                player.animator.loops = false;
-                sprite.index = player.animator.index;
-                player.animator.tick(time.delta()); 
-                break 'player_loop;
-            }
-            if enemy.punch_timer < enemy::PUNCH_DURATION && enemy.punch_timer >= 0. {
-                if !player.blocking && player.dodge_timer == 0. && player.is_hit_timer <= 0. {
-                    let state = player.states.hit;
-                    player.animator.set_indices(state);
-                    player.animator.set_frametime(0.1);
-                    player.animator.loops = false;
-                    player.is_hit_timer = IS_HIT_TIMER;
-                    player.punch_timer = 0.;
-                    player.hits_taken_total += 1;
-
-                    sprite.index = player.animator.index;
-                    player.animator.tick(time.delta()); 
-                    continue 'player_loop;
-                }
-            }
+    for (entity, mut player, mut translation, mut sprite) in &mut player_query {
+        player.fighter.check_ko();
 
-            // not dodging
-            match (player.dodge_timer, player.punch_timer, player.is_hit_timer) {
-                (dodge_timer, punch_timer, is_hit_timer) if dodge_timer <= 0. && punch_timer <= 0. && is_hit_timer <= 0. => {
-                    // put player at origin
-                    transform.translation = player.origin;
-                    player.blocking = false;
-
-                    let (blocking, left_dodge, right_dodge, left_punch, right_punch) = (
-                        input.pressed(KeyCode::Down),
-                        input.just_pressed(KeyCode::Left),
-                        input.just_pressed(KeyCode::Right),
-                        input.just_pressed(KeyCode::Z),
-                        input.just_pressed(KeyCode::X)
-                    );
-
-                    match (blocking, left_dodge, right_dodge, left_punch, right_punch) {
-                        // blocking
-                        (true, _, _, _, _) => {
-                            let state = player.states.block;
-                            player.animator.set_indices(state);
-                            player.animator.set_frametime(0.1);
-                            player.animator.loops = false;
-
-                            player.blocking = true;
-                        },
-                        // left move
-                        (_, true, _, _, _) => {
-                            sprite.flip_x = false;
-                            let state = player.states.dodge;
-                            player.animator.set_indices(state);
-                            player.animator.set_frametime(0.1);
-                            player.animator.loops = false;
-                            player.action_dir = ActionDirection::Left;
-                            player.dodge_timer = DODGE_DURATION;
-                        },
-                        // right move
-                        (_, _, true, _, _) => {
-                            sprite.flip_x = true;
-                            let state = player.states.dodge;
-                            player.animator.set_indices(state);
-                            player.animator.set_frametime(0.1);
-                            player.animator.loops = false;
-                            player.action_dir = ActionDirection::Right;
-                            player.dodge_timer = DODGE_DURATION;
-                        }
-                        // left punch
-                        (_, _, _, true, _) => {
-                            sprite.flip_x = false;
-                            let state = player.states.punch;
-                            player.animator.set_indices(state);
-                            player.animator.set_frametime(0.03);
-                            player.animator.loops = false;
-                            player.action_dir = ActionDirection::Left;
-                            player.punch_timer = PUNCH_DURATION;
-                        },
-                        // right punch
-                        (_, _, _, _, true) => {
-                            sprite.flip_x = true;
-                            let state = player.states.punch;
-                            player.animator.set_indices(state);
-                            player.animator.set_frametime(0.03);
-                            player.animator.loops = false;
-                            player.action_dir = ActionDirection::Right;
-                            player.punch_timer = PUNCH_DURATION;
-                        },
-                        // nothing
-                        _ => {
-                            let state = player.states.idle;
-                            player.animator.set_indices(state);
-                            player.animator.set_frametime(0.3);
-                            player.animator.loops = true;
-                            player.dodge_timer = 0.0;
-                        }
-                    }
+        let tired = player.is_tired();
+        sprite.color = if tired { TIRED_COLOR } else { Color::WHITE };
 
+        let fighter = &mut player.fighter;
+        if fighter.finished() {
+            match fighter.state {
+                FighterState::Dodging(_) | FighterState::Punching(_) | FighterState::Hit => {
+                    fighter.enter(FighterState::Idle, 0.0)
                 },
-                (_, _, is_hit_timer) if is_hit_timer > 0. => {
-                    if player.is_hit_timer < IS_HIT_TIMER {
-                        let state = player.states.hit;
-                        player.animator.set_indices(state);
-                        player.animator.set_frametime(0.3);
+                _ => {}
+            }
+        }
+
+        if matches!(fighter.state, FighterState::Idle | FighterState::Blocking) {
+            match (input.block, input.dodge_left, input.dodge_right, input.punch_left, input.punch_right) {
+                // blocking
+                (true, _, _, _, _) => {
+                    if fighter.state != FighterState::Blocking {
+                        fighter.enter(FighterState::Blocking, 0.0);
                     }
-                    player.is_hit_timer -= time.delta_seconds()
+                },
+                // left move
+                (_, true, _, _, _) => {
+                    sprite.flip_x = false;
+                    fighter.enter(FighterState::Dodging(Side::Left), DODGE_DURATION);
+                },
+                // right move
+                (_, _, true, _, _) => {
+                    sprite.flip_x = true;
+                    fighter.enter(FighterState::Dodging(Side::Right), DODGE_DURATION);
                 }
-                (dodge_timer, punch_timer, _) if dodge_timer > 0. && punch_timer <= 0. => {
-                    player.blocking = false;
-                    let x = (1.0 - (player.dodge_timer / DODGE_DURATION)) * 2.0;
-                    let x = x - 1.0;
-                    let x = x*x*x*x;
-                    transform.translation = player.origin
-                        + Vec3::new(
-                            match player.action_dir {
-                                ActionDirection::None => 0.0,
-                                ActionDirection::Left => x - 1.0,
-                                ActionDirection::Right => -x + 1.0,
-                            } * DODGE_DISTANCE,
-                            0.0,
-                            0.0,
-                        );
-                    player.dodge_timer -= time.delta_seconds()
+                // left punch
+                (_, _, _, true, _) if !tired => {
+                    sprite.flip_x = false;
+                    fighter.enter(FighterState::Punching(Side::Left), PUNCH_DURATION);
+                    attacks.send(AttackEvent {
+                        attacker: entity,
+                        side: Side::Left,
+                        damage: PUNCH_DAMAGE,
+                        window: PUNCH_WINDOW,
+                    });
                 },
-                (_, punch_timer, _) if punch_timer > 0. => {
-                    let x = (1.0 - (player.punch_timer / PUNCH_DURATION)) * 2.0;
-                    let x = x - 1.0;
-                    let x = x*x*x*x;
-                    transform.translation = player.origin
-                        + Vec3::new(
-                            match player.action_dir {
-                                ActionDirection::None => 0.0,
-                                ActionDirection::Left => x - 1.0,
-                                ActionDirection::Right => -x + 1.0,
-                            } * -5.0,
-                            -x * 15.0,
-                            0.0,
-                        );
-                    player.punch_timer -= time.delta_seconds()
+                // right punch
+                (_, _, _, _, true) if !tired => {
+                    sprite.flip_x = true;
+                    fighter.enter(FighterState::Punching(Side::Right), PUNCH_DURATION);
+                    attacks.send(AttackEvent {
+                        attacker: entity,
+                        side: Side::Right,
+                        damage: PUNCH_DAMAGE,
+                        window: PUNCH_WINDOW,
+                    });
                 },
-                _ => {}
+                // nothing
+                _ => {
+                    if fighter.state != FighterState::Idle {
+                        fighter.enter(FighterState::Idle, 0.0);
+                    }
+                }
+            }
+        }
+
+        match fighter.state {
+            FighterState::Dodging(side) => {
+                let x = fighter.progress(DODGE_DURATION) * 2.0 - 1.0;
+                let x = x*x*x*x;
+                fighter.offset = Vec3::new(side.sign() * (1.0 - x) * DODGE_DISTANCE, 0.0, 0.0);
+            },
+            FighterState::Punching(side) => {
+                let x = fighter.progress(PUNCH_DURATION) * 2.0 - 1.0;
+                let x = x*x*x*x;
+                fighter.offset = Vec3::new(side.sign() * (1.0 - x) * -5.0, -x * 15.0, 0.0);
+            },
+            _ => {}
+        }
+
+        translation.current = fighter.translation();
+        sprite.index = fighter.animator.index;
+        fighter.tick(time.delta());
+    }
+}
+
+/// Spends and refunds hearts based on how the last tick's punches went.
+pub fn update_hearts(
+    mut hits: EventReader<HitEvent>,
+    mut blocks: EventReader<BlockedEvent>,
+    mut dodges: EventReader<DodgedEvent>,
+    mut player_query: Query<(Entity, &mut Player)>,
+) {
+    for (entity, mut player) in &mut player_query {
+        for hit in hits.read() {
+            if hit.target == entity {
+                player.lose_hearts(HEARTS_LOST_WHEN_HIT);
+            } else if hit.attacker == entity {
+                player.gain_hearts(HEARTS_GAINED_ON_HIT);
+            }
+        }
+        for block in blocks.read() {
+            if block.attacker == entity {
+                player.lose_hearts(HEARTS_LOST_WHEN_BLOCKED);
+            }
+        }
+        for dodge in dodges.read() {
+            if dodge.target == entity && player.is_tired() {
+                player.gain_hearts(HEARTS_GAINED_ON_TIRED_DODGE);
             }
-            sprite.index = player.animator.index;
-            player.animator.tick(time.delta());
         }
     }
 }
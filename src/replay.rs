@@ -0,0 +1,295 @@
+//! Recording and playback of the player's input, one entry per combat tick.
+//! Together with the combat seed and how each fight was set up (the rounds,
+//! the challenger and any training action) this is enough to replay a
+//! session's fights exactly.
+//!
+//! Files are RON with the input run-length encoded as `(ticks, buttons)`
+//! pairs, where `buttons` is a [`PlayerInput`] bitmask. Training actions
+//! picked mid-fight are kept as `(tick, action)` pairs:
+//!
+//! ```ron
+//! (
+//!     version: 2,
+//!     seed: 1234,
+//!     rounds: 3,
+//!     round_length: 180.0,
+//!     fights: [(beaten: 0, training: None), (beaten: 0, training: Some(BlockOnly))],
+//!     training_actions: [(150, PunchLeftOnly)],
+//!     inputs: [(90, 0), (1, 8), (40, 0), (30, 1)],
+//! )
+//! ```
+
+use std::{fmt, fs, path::PathBuf};
+
+use bevy::{app::AppExit, prelude::*};
+use ron::error::SpannedError;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    controls::PlayerInput,
+    roster::Progress,
+    round::Round,
+    training::{FrameData, Training, TrainingAction},
+};
+
+const REPLAY_VERSION: u32 = 2;
+
+/// How one fight was set up, in the order the fights were started.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default, Debug)]
+pub struct FightSetup {
+    /// [`Progress::beaten`] at the time, which picks the challenger.
+    pub beaten: usize,
+    /// The enemy action the fight started with, if it was training.
+    pub training: Option<TrainingAction>,
+}
+
+#[derive(Default, Clone, PartialEq, Debug)]
+pub struct Replay {
+    pub seed: u64,
+    pub rounds: u32,
+    /// Seconds per round.
+    pub round_length: f32,
+    pub fights: Vec<FightSetup>,
+    /// Training actions picked from the pause menu, by the tick they apply from.
+    pub training_actions: Vec<(usize, TrainingAction)>,
+    pub inputs: Vec<PlayerInput>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ReplayFile {
+    version: u32,
+    seed: u64,
+    rounds: u32,
+    round_length: f32,
+    fights: Vec<FightSetup>,
+    training_actions: Vec<(usize, TrainingAction)>,
+    inputs: Vec<(u32, u8)>,
+}
+
+/// Read on its own first, so an old replay reports its version rather than
+/// a missing field.
+#[derive(Deserialize)]
+struct ReplayHeader {
+    version: u32,
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(std::io::Error),
+    Parse(SpannedError),
+    Write(ron::Error),
+    Version(u32),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Io(err) => write!(f, "could not access replay file: {err}"),
+            ReplayError::Parse(err) => write!(
+                f,
+                "could not parse replay file at {}:{}: {}",
+                err.position.line, err.position.col, err.code
+            ),
+            ReplayError::Write(err) => write!(f, "could not write replay: {err}"),
+            ReplayError::Version(version) => write!(f, "unsupported replay version {version}"),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl From<std::io::Error> for ReplayError {
+    fn from(err: std::io::Error) -> Self {
+        ReplayError::Io(err)
+    }
+}
+
+impl From<SpannedError> for ReplayError {
+    fn from(err: SpannedError) -> Self {
+        ReplayError::Parse(err)
+    }
+}
+
+impl From<ron::Error> for ReplayError {
+    fn from(err: ron::Error) -> Self {
+        ReplayError::Write(err)
+    }
+}
+
+impl Replay {
+    pub fn new(seed: u64) -> Replay {
+        let round = Round::default();
+        Replay {
+            seed,
+            rounds: round.count,
+            round_length: round.length,
+            ..Replay::default()
+        }
+    }
+
+    pub fn to_ron(&self) -> Result<String, ReplayError> {
+        let mut runs: Vec<(u32, u8)> = Vec::new();
+        for input in &self.inputs {
+            let bits = input.to_bits();
+            match runs.last_mut() {
+                Some((count, last)) if *last == bits => *count += 1,
+                _ => runs.push((1, bits)),
+            }
+        }
+        let file = ReplayFile {
+            version: REPLAY_VERSION,
+            seed: self.seed,
+            rounds: self.rounds,
+            round_length: self.round_length,
+            fights: self.fights.clone(),
+            training_actions: self.training_actions.clone(),
+            inputs: runs,
+        };
+        Ok(ron::ser::to_string_pretty(&file, ron::ser::PrettyConfig::default())?)
+    }
+
+    pub fn from_ron(text: &str) -> Result<Replay, ReplayError> {
+        // older replays don't say how their fights were set up, so they
+        // can't be played back faithfully
+        let header: ReplayHeader = ron::from_str(text)?;
+        if header.version != REPLAY_VERSION {
+            return Err(ReplayError::Version(header.version));
+        }
+        let file: ReplayFile = ron::from_str(text)?;
+        let inputs = file
+            .inputs
+            .into_iter()
+            .flat_map(|(count, bits)| std::iter::repeat_n(PlayerInput::from_bits(bits), count as usize))
+            .collect();
+        Ok(Replay {
+            seed: file.seed,
+            rounds: file.rounds,
+            round_length: file.round_length,
+            fights: file.fights,
+            training_actions: file.training_actions,
+            inputs,
+        })
+    }
+}
+
+/// Present while recording; written to `path` when the app exits.
+#[derive(Resource)]
+pub struct Recorder {
+    pub path: Option<PathBuf>,
+    pub replay: Replay,
+}
+
+/// Present while replaying; removed once every recorded tick has been played.
+#[derive(Resource)]
+pub struct Playback {
+    pub replay: Replay,
+    pub tick: usize,
+    /// How many fights have been set up so far.
+    pub fights: usize,
+}
+
+impl Playback {
+    pub fn new(replay: Replay) -> Playback {
+        Playback {
+            replay,
+            tick: 0,
+            fights: 0,
+        }
+    }
+}
+
+pub fn load_replay(path: &str) -> Result<Replay, ReplayError> {
+    Replay::from_ron(&fs::read_to_string(path)?)
+}
+
+/// Sets a fight up the way the next recorded one was. Exclusive so that the
+/// rest of the fight's setup already sees whether it is training.
+pub fn set_up_replayed_fight(world: &mut World) {
+    let mut playback = world.resource_mut::<Playback>();
+    let setup = playback.replay.fights.get(playback.fights).copied();
+    playback.fights += 1;
+    let round = Round::new(playback.replay.rounds, playback.replay.round_length);
+    world.insert_resource(round);
+    let Some(setup) = setup else {
+        warn!("replay has no more fights recorded");
+        return;
+    };
+    if let Some(mut progress) = world.get_resource_mut::<Progress>() {
+        progress.beaten = setup.beaten;
+    }
+    match setup.training {
+        Some(action) => {
+            world.insert_resource(Training { action });
+            world.init_resource::<FrameData>();
+        }
+        None => {
+            world.remove_resource::<Training>();
+            world.remove_resource::<FrameData>();
+        }
+    }
+}
+
+pub fn record_fight_setup(
+    mut recorder: ResMut<Recorder>,
+    round: Res<Round>,
+    progress: Option<Res<Progress>>,
+    training: Option<Res<Training>>,
+) {
+    recorder.replay.rounds = round.count;
+    recorder.replay.round_length = round.length;
+    recorder.replay.fights.push(FightSetup {
+        beaten: progress.map_or(0, |progress| progress.beaten),
+        training: training.map(|training| training.action),
+    });
+}
+
+/// Notes a training action picked from the pause menu, to apply from the
+/// next tick on.
+pub fn record_training_action(mut recorder: ResMut<Recorder>, training: Res<Training>) {
+    if training.is_changed() && !training.is_added() {
+        let tick = recorder.replay.inputs.len();
+        recorder.replay.training_actions.push((tick, training.action));
+    }
+}
+
+/// Replaces whatever the keyboard produced with the recorded input.
+pub fn play_back_input(
+    mut commands: Commands,
+    mut playback: ResMut<Playback>,
+    mut input: ResMut<PlayerInput>,
+    training: Option<ResMut<Training>>,
+) {
+    let tick = playback.tick;
+    if let Some(mut training) = training {
+        for (_, action) in playback.replay.training_actions.iter().filter(|(at, _)| *at == tick) {
+            training.action = *action;
+        }
+    }
+    match playback.replay.inputs.get(playback.tick) {
+        Some(recorded) => {
+            *input = *recorded;
+            playback.tick += 1;
+        }
+        None => {
+            info!("replay finished after {} ticks", playback.tick);
+            commands.remove_resource::<Playback>();
+        }
+    }
+}
+
+pub fn record_input(mut recorder: ResMut<Recorder>, input: Res<PlayerInput>) {
+    recorder.replay.inputs.push(*input);
+}
+
+pub fn save_recording(mut exit: EventReader<AppExit>, recorder: Res<Recorder>) {
+    if exit.read().next().is_none() {
+        return;
+    }
+    let Some(path) = &recorder.path else {
+        return;
+    };
+    match recorder.replay.to_ron().and_then(|text| Ok(fs::write(path, text)?)) {
+        Ok(()) => info!("saved replay to {}", path.display()),
+        Err(err) => error!("{}: {err}", path.display()),
+    }
+}
@@ -0,0 +1,51 @@
+//! The single source of randomness for combat. Every fight is driven by a
+//! seed that is logged at startup, so a fight can be replayed by passing the
+//! same seed back in with `--seed <n>`. The generator is ChaCha8 rather than
+//! `StdRng`, whose algorithm may change between rand releases and would
+//! silently invalidate recorded seeds.
+
+use bevy::ecs::system::Resource;
+use rand::{Error, RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+#[derive(Resource)]
+pub struct CombatRng {
+    seed: u64,
+    rng: ChaCha8Rng,
+}
+
+impl CombatRng {
+    pub fn new(seed: u64) -> CombatRng {
+        CombatRng {
+            seed,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+        }
+    }
+
+    /// Uses `seed` if one was given, otherwise picks a fresh one.
+    pub fn from_seed_or_random(seed: Option<u64>) -> CombatRng {
+        CombatRng::new(seed.unwrap_or_else(rand::random))
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+impl RngCore for CombatRng {
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.rng.try_fill_bytes(dest)
+    }
+}
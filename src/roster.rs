@@ -0,0 +1,356 @@
+//! The opponents, grouped into circuits and fought in order, loaded from
+//! `*.roster.ron`. Beating the next challenger unlocks the one after it;
+//! once the last one is beaten the player defends the title against them.
+//!
+//! ```ron
+//! Roster(
+//!     circuits: [
+//!         (
+//!             name: "MINOR CIRCUIT",
+//!             music: Some("music/minor_circuit.wav"),
+//!             opponents: ["fighters/abigail.fighter.ron"],
+//!         ),
+//!     ],
+//! )
+//! ```
+
+use std::{collections::HashMap, fmt};
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    prelude::*,
+    utils::BoxedFuture,
+};
+use ron::error::SpannedError;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    fighter_def::{FighterDef, FighterRole, FighterSource},
+    loading::{GameAssets, LoadErrors},
+    round::{Finish, Round},
+    state::{self, AppState},
+};
+
+#[derive(Deserialize)]
+#[serde(rename = "Roster")]
+struct RosterFile {
+    circuits: Vec<CircuitFile>,
+}
+
+#[derive(Deserialize)]
+struct CircuitFile {
+    name: String,
+    #[serde(default)]
+    music: Option<String>,
+    opponents: Vec<String>,
+}
+
+/// The loader requests every opponent, so a roster only counts as fully
+/// loaded once all of their definitions and sheets are in.
+#[derive(Asset, TypePath)]
+pub struct Roster {
+    pub circuits: Vec<Circuit>,
+}
+
+pub struct Circuit {
+    pub name: String,
+    /// Looped during fights in this circuit, instead of the default track.
+    pub music: Option<String>,
+    pub opponents: Vec<Handle<FighterDef>>,
+}
+
+impl Roster {
+    /// Every opponent in the order they are fought.
+    pub fn opponents(&self) -> impl Iterator<Item = &Handle<FighterDef>> {
+        self.circuits.iter().flat_map(|circuit| &circuit.opponents)
+    }
+
+    /// The circuit `opponent` belongs to.
+    pub fn circuit_of(&self, opponent: &Handle<FighterDef>) -> Option<&Circuit> {
+        self.circuits.iter().find(|circuit| circuit.opponents.contains(opponent))
+    }
+
+    /// The opponent to fight next; the loader makes sure there is one.
+    pub fn challenger(&self, progress: &Progress) -> &Handle<FighterDef> {
+        let count = self.opponents().count();
+        self.opponents()
+            .nth(progress.beaten.min(count - 1))
+            .expect("roster has no opponents")
+    }
+}
+
+#[derive(Debug)]
+pub enum RosterError {
+    Io(std::io::Error),
+    Parse(SpannedError),
+    Empty,
+}
+
+impl fmt::Display for RosterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RosterError::Io(err) => write!(f, "could not read roster file: {err}"),
+            RosterError::Parse(err) => write!(
+                f,
+                "could not parse roster file at {}:{}: {}",
+                err.position.line, err.position.col, err.code
+            ),
+            RosterError::Empty => write!(f, "roster has no opponents"),
+        }
+    }
+}
+
+impl std::error::Error for RosterError {}
+
+impl From<std::io::Error> for RosterError {
+    fn from(err: std::io::Error) -> Self {
+        RosterError::Io(err)
+    }
+}
+
+impl From<SpannedError> for RosterError {
+    fn from(err: SpannedError) -> Self {
+        RosterError::Parse(err)
+    }
+}
+
+pub struct RosterLoader {
+    errors: LoadErrors,
+}
+
+impl FromWorld for RosterLoader {
+    fn from_world(world: &mut World) -> Self {
+        RosterLoader {
+            errors: world.get_resource_or_insert_with(LoadErrors::default).clone(),
+        }
+    }
+}
+
+impl AssetLoader for RosterLoader {
+    type Asset = Roster;
+    type Settings = ();
+    type Error = RosterError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Roster, RosterError>> {
+        Box::pin(async move {
+            let result = async {
+                let mut bytes = Vec::new();
+                reader.read_to_end(&mut bytes).await?;
+                let file: RosterFile = ron::de::from_bytes(&bytes)?;
+                if file.circuits.iter().all(|circuit| circuit.opponents.is_empty()) {
+                    return Err(RosterError::Empty);
+                }
+                let circuits = file
+                    .circuits
+                    .into_iter()
+                    .map(|circuit| Circuit {
+                        name: circuit.name,
+                        music: circuit.music,
+                        opponents: circuit.opponents.iter().map(|path| load_context.load(path)).collect(),
+                    })
+                    .collect();
+                Ok(Roster { circuits })
+            }
+            .await;
+            self.errors.record(load_context.path(), result)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["roster.ron"]
+    }
+}
+
+/// Wins and losses against one opponent.
+#[derive(Serialize, Deserialize, Clone, Copy, Default, Debug)]
+#[serde(default)]
+pub struct Record {
+    pub wins: u32,
+    pub losses: u32,
+    /// Wins by KO or TKO.
+    pub kos: u32,
+    /// Fastest win by KO or TKO, in seconds of fight clock.
+    pub best_ko: Option<f32>,
+}
+
+/// How far the player has come through the roster.
+#[derive(Resource, Serialize, Deserialize, Default, Clone, Debug)]
+#[serde(default)]
+pub struct Progress {
+    /// Opponents beaten so far, in roster order.
+    pub beaten: usize,
+    /// Keyed by the opponent's definition path.
+    pub records: HashMap<String, Record>,
+    /// Career record brought in with a password, not tied to any opponent.
+    pub carried_over: Record,
+}
+
+impl Progress {
+    /// Counts a fight against the opponent at `index`; winning against the
+    /// challenger unlocks the next one. `ko_time` is set for wins inside
+    /// the distance.
+    pub fn record(&mut self, opponent: String, index: usize, won: bool, ko_time: Option<f32>) {
+        let record = self.records.entry(opponent).or_default();
+        if let Some(time) = ko_time {
+            record.kos += 1;
+            record.best_ko = Some(record.best_ko.map_or(time, |best| best.min(time)));
+        }
+        if won {
+            record.wins += 1;
+            if index == self.beaten {
+                self.beaten += 1;
+            }
+        } else {
+            record.losses += 1;
+        }
+    }
+
+    pub fn is_unlocked(&self, index: usize) -> bool {
+        index <= self.beaten
+    }
+
+    /// Wins, losses and KOs against everyone.
+    pub fn career(&self) -> Record {
+        self.records.values().fold(self.carried_over, |total, record| Record {
+            wins: total.wins + record.wins,
+            losses: total.losses + record.losses,
+            kos: total.kos + record.kos,
+            best_ko: match (total.best_ko, record.best_ko) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            },
+        })
+    }
+}
+
+fn def_key(def: &Handle<FighterDef>) -> String {
+    def.path().map(ToString::to_string).unwrap_or_default()
+}
+
+/// The name on the definition, or its file name without extensions.
+fn opponent_name(def: &Handle<FighterDef>, defs: &Assets<FighterDef>) -> String {
+    match defs.get(def) {
+        Some(loaded) if !loaded.name.is_empty() => loaded.name.clone(),
+        _ => def
+            .path()
+            .and_then(|path| path.path().file_name()?.to_str()?.split('.').next().map(str::to_uppercase))
+            .unwrap_or_default(),
+    }
+}
+
+/// Runs as the result screen opens, while the fight is still spawned.
+pub fn record_fight(
+    state: Res<State<AppState>>,
+    assets: Res<GameAssets>,
+    rosters: Res<Assets<Roster>>,
+    round: Res<Round>,
+    mut progress: ResMut<Progress>,
+    sources: Query<&FighterSource>,
+) {
+    let Some(roster) = rosters.get(&assets.roster) else {
+        return;
+    };
+    let Some(source) = sources.iter().find(|source| source.role == FighterRole::Enemy) else {
+        return;
+    };
+    let Some(index) = roster.opponents().position(|def| *def == source.def) else {
+        return;
+    };
+    let won = *state.get() == AppState::Victory;
+    let ko_time = match round.result {
+        Some(result) if won && result.finish != Finish::Decision => Some(round.elapsed),
+        _ => None,
+    };
+    progress.record(def_key(&source.def), index, won, ko_time);
+}
+
+pub fn spawn_circuit(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    assets: Res<GameAssets>,
+    rosters: Res<Assets<Roster>>,
+    defs: Res<Assets<FighterDef>>,
+    progress: Res<Progress>,
+) {
+    let Some(roster) = rosters.get(&assets.roster) else {
+        return;
+    };
+    let challenger = roster.challenger(&progress);
+
+    let mut lines = Vec::new();
+    let mut index = 0;
+    for circuit in &roster.circuits {
+        lines.push(circuit.name.clone());
+        for def in &circuit.opponents {
+            lines.push(if progress.is_unlocked(index) {
+                let record = progress.records.get(&def_key(def)).copied().unwrap_or_default();
+                let marker = if def == challenger { "> " } else { "" };
+                let best_ko = record
+                    .best_ko
+                    .map(|time| format!("  KO {}:{:02}", time as u32 / 60, time as u32 % 60))
+                    .unwrap_or_default();
+                format!("{marker}{}  {}-{}{best_ko}", opponent_name(def, &defs), record.wins, record.losses)
+            } else {
+                "???".to_string()
+            });
+            index += 1;
+        }
+        lines.push(String::new());
+    }
+    let career = progress.career();
+    lines.push(format!("RECORD {}-{}  {} KO", career.wins, career.losses, career.kos));
+    let next = opponent_name(challenger, &defs);
+    if progress.beaten >= index {
+        lines.push(format!("CHAMPION! TITLE DEFENSE VS {next}"));
+    } else {
+        lines.push(format!("NEXT CHALLENGER: {next}"));
+    }
+    lines.push("\n\nPRESS ENTER".to_string());
+    state::spawn_screen(&mut commands, &asset_server, lines.join("\n"));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_beating_the_challenger_unlocks_the_next() {
+        let mut progress = Progress::default();
+        progress.record("a".to_string(), 0, false, None);
+        assert_eq!(progress.beaten, 0);
+        progress.record("a".to_string(), 0, true, Some(90.0));
+        assert_eq!(progress.beaten, 1);
+        assert!(progress.is_unlocked(1) && !progress.is_unlocked(2));
+
+        // a rematch with someone already beaten unlocks nothing
+        progress.record("a".to_string(), 0, true, Some(60.0));
+        assert_eq!(progress.beaten, 1);
+        let record = progress.records["a"];
+        assert_eq!((record.wins, record.losses, record.best_ko), (2, 1, Some(60.0)));
+    }
+
+    #[test]
+    fn shipped_roster_fills_every_circuit_with_its_own_fighters() {
+        let assets = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("assets");
+        let text = std::fs::read_to_string(assets.join("circuits.roster.ron")).unwrap();
+        let roster: RosterFile = ron::from_str(&text).unwrap();
+        let names: Vec<&str> = roster.circuits.iter().map(|circuit| circuit.name.as_str()).collect();
+        assert_eq!(names, ["MINOR CIRCUIT", "MAJOR CIRCUIT", "WORLD CIRCUIT"]);
+
+        let mut atlases = Vec::new();
+        for circuit in &roster.circuits {
+            assert!(!circuit.opponents.is_empty(), "{} has no opponents", circuit.name);
+            for path in &circuit.opponents {
+                let def = FighterDef::from_bytes(&std::fs::read(assets.join(path)).unwrap()).unwrap();
+                assert!(assets.join(&def.atlas).exists(), "{path}: no {}", def.atlas);
+                assert!(!atlases.contains(&def.atlas), "{path} shares {}", def.atlas);
+                atlases.push(def.atlas);
+            }
+        }
+    }
+}
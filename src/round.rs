@@ -0,0 +1,185 @@
+//! Fights are split into timed rounds. The clock only runs while the round
+//! is live; when it runs out the bell freezes both fighters, and an
+//! intermission gives each of them some health and hearts back before the
+//! next round starts.
+
+use bevy::prelude::*;
+
+use crate::{attack::ActiveAttacks, enemy::Enemy, fighter_def::FighterRole, player::Player};
+
+const BELL_DURATION: f32 = 2.0;
+const INTERMISSION_DURATION: f32 = 5.0;
+/// How long the result stays up before the fight ends.
+const RESULT_DURATION: f32 = 3.0;
+/// Share of the missing health each fighter gets back between rounds.
+const HEALTH_RECOVERY: f32 = 0.5;
+/// Share of the missing hearts the player gets back between rounds.
+const HEART_RECOVERY: f32 = 0.5;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RoundPhase {
+    Fighting,
+    /// The round just ended; everyone stands still while the bell rings.
+    Bell,
+    Intermission,
+    /// Someone is down and the referee is counting; the clock is stopped.
+    Knockdown,
+    /// The last round has ended or someone was knocked out.
+    Over,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum Finish {
+    KO,
+    TKO,
+    /// Went the distance; whoever has more health left wins.
+    Decision,
+}
+
+impl Finish {
+    pub fn label(self) -> &'static str {
+        match self {
+            Finish::KO => "KO",
+            Finish::TKO => "TKO",
+            Finish::Decision => "DECISION",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct FightResult {
+    pub winner: FighterRole,
+    pub finish: Finish,
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct Round {
+    /// Starts at 1.
+    pub number: u32,
+    pub count: u32,
+    /// Seconds per round.
+    pub length: f32,
+    pub phase: RoundPhase,
+    /// Seconds left in the current phase.
+    pub timer: f32,
+    /// Set once the fight is over.
+    pub result: Option<FightResult>,
+    /// Seconds the clock has run this fight, across rounds.
+    pub elapsed: f32,
+}
+
+/// Rung when a round starts or ends.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct BellEvent;
+
+impl Round {
+    pub fn new(count: u32, length: f32) -> Round {
+        Round {
+            number: 1,
+            count,
+            length,
+            phase: RoundPhase::Fighting,
+            timer: length,
+            result: None,
+            elapsed: 0.,
+        }
+    }
+
+    /// Back to the start of round one, keeping the count and length.
+    pub fn restart(&mut self) {
+        *self = Round::new(self.count, self.length);
+    }
+
+    pub fn finish(&mut self, winner: FighterRole, finish: Finish) {
+        self.phase = RoundPhase::Over;
+        self.timer = RESULT_DURATION;
+        self.result = Some(FightResult { winner, finish });
+    }
+
+    pub fn is_last(&self) -> bool {
+        self.number >= self.count
+    }
+
+    /// The round clock as `m:ss`, rounded up so it reads `0:00` only at the bell.
+    pub fn clock(&self) -> String {
+        let secs = match self.phase {
+            RoundPhase::Fighting | RoundPhase::Knockdown => self.timer.max(0.).ceil() as u32,
+            _ => 0,
+        };
+        format!("{}:{:02}", secs / 60, secs % 60)
+    }
+}
+
+impl Default for Round {
+    /// Three rounds of three minutes.
+    fn default() -> Self {
+        Round::new(3, 180.0)
+    }
+}
+
+/// Run condition for everything that should stop when the bell rings.
+pub fn round_in_progress(round: Res<Round>) -> bool {
+    round.phase == RoundPhase::Fighting
+}
+
+pub fn update_round(
+    time: Res<Time>,
+    mut round: ResMut<Round>,
+    mut bells: EventWriter<BellEvent>,
+    mut attacks: ResMut<ActiveAttacks>,
+    mut player_query: Query<&mut Player>,
+    mut enemy_query: Query<&mut Enemy>,
+) {
+    // the count stops the clock
+    if round.phase == RoundPhase::Knockdown {
+        return;
+    }
+    if round.phase == RoundPhase::Fighting {
+        round.elapsed += time.delta_seconds();
+    }
+    round.timer -= time.delta_seconds();
+    if round.timer > 0. {
+        return;
+    }
+
+    match round.phase {
+        RoundPhase::Fighting => {
+            bells.send(BellEvent);
+            attacks.clear();
+            round.phase = RoundPhase::Bell;
+            round.timer = BELL_DURATION;
+        }
+        RoundPhase::Bell if round.is_last() => {
+            let player = player_query.iter().map(|player| player.fighter.health_fraction()).next();
+            let enemy = enemy_query.iter().map(|enemy| enemy.fighter.health_fraction()).next();
+            let winner = if player >= enemy { FighterRole::Player } else { FighterRole::Enemy };
+            round.finish(winner, Finish::Decision);
+        }
+        RoundPhase::Bell => {
+            for mut player in &mut player_query {
+                player.recover(HEALTH_RECOVERY, HEART_RECOVERY);
+            }
+            for mut enemy in &mut enemy_query {
+                enemy.fighter.recover(HEALTH_RECOVERY);
+            }
+            round.phase = RoundPhase::Intermission;
+            round.timer = INTERMISSION_DURATION;
+        }
+        RoundPhase::Intermission => {
+            for mut player in &mut player_query {
+                player.fighter.reset();
+                player.fighter.knockdowns = 0;
+            }
+            for mut enemy in &mut enemy_query {
+                enemy.fighter.reset();
+                enemy.fighter.knockdowns = 0;
+            }
+            bells.send(BellEvent);
+            round.number += 1;
+            round.phase = RoundPhase::Fighting;
+            round.timer = round.length;
+        }
+        RoundPhase::Knockdown | RoundPhase::Over => {}
+    }
+}
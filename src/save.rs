@@ -0,0 +1,324 @@
+//! Career progress and settings, kept between sessions in `save.ron` under
+//! the platform's data directory (`~/.local/share/crucible` on Linux) and
+//! rewritten whenever either of them changes.
+//!
+//! ```ron
+//! (
+//!     version: 1,
+//!     progress: (
+//!         beaten: 1,
+//!         records: {
+//!             "fighters/abigail.fighter.ron": (wins: 1, losses: 2, kos: 1, best_ko: Some(74.5)),
+//!         },
+//!     ),
+//!     settings: (rounds: 3, round_length: 180.0, music_volume: 0.6),
+//! )
+//! ```
+//!
+//! Every field has a default, so a save written before a field existed still
+//! loads, and one from before the `version` field is read as version 1. A
+//! save from a newer build is left alone and nothing is saved that session. A
+//! save that can't be parsed at all is moved aside to `save.ron.bak` and the
+//! game starts over rather than refusing to run.
+
+use std::{
+    env, fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use bevy::prelude::*;
+use ron::error::SpannedError;
+use serde::{Deserialize, Serialize};
+
+use crate::{replay::Playback, roster::Progress};
+
+const SAVE_VERSION: u32 = 1;
+
+/// Player preferences, saved along with their progress.
+#[derive(Resource, Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct Settings {
+    pub rounds: u32,
+    /// Seconds per round.
+    pub round_length: f32,
+    /// Volume levels from 0 to 1; music and effects are scaled by `master`.
+    pub master_volume: f32,
+    pub music_volume: f32,
+    pub sfx_volume: f32,
+    /// Ticks the fight freezes for after a clean hit; 0 turns hit-stop off.
+    pub hit_stop_frames: u32,
+    /// Scales screen shake from 0 (off) to 1.
+    pub screen_shake: f32,
+}
+
+impl Default for Settings {
+    /// Three rounds of three minutes.
+    fn default() -> Self {
+        Settings {
+            rounds: 3,
+            round_length: 180.0,
+            master_volume: 1.0,
+            music_volume: 0.6,
+            sfx_volume: 1.0,
+            hit_stop_frames: 4,
+            screen_shake: 1.0,
+        }
+    }
+}
+
+impl Settings {
+    pub fn sfx_level(&self) -> f32 {
+        self.master_volume * self.sfx_volume
+    }
+
+    pub fn music_level(&self) -> f32 {
+        self.master_volume * self.music_volume
+    }
+}
+
+#[derive(Default, Debug)]
+pub struct Save {
+    pub progress: Progress,
+    pub settings: Settings,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+struct SaveFile {
+    version: u32,
+    progress: Progress,
+    settings: Settings,
+}
+
+/// Read on its own first, to pick how to read the rest.
+#[derive(Deserialize)]
+struct SaveHeader {
+    #[serde(default = "unversioned")]
+    version: u32,
+}
+
+/// Saves from before the version field share version 1's layout.
+fn unversioned() -> u32 {
+    1
+}
+
+/// Where the save lives; missing when the platform has no data directory or
+/// the existing save mustn't be touched, in which case nothing is saved.
+#[derive(Resource)]
+pub struct SavePath(pub PathBuf);
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(std::io::Error),
+    Parse(SpannedError),
+    Write(ron::Error),
+    Version(u32),
+    Newer(u32),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveError::Io(err) => write!(f, "could not access save file: {err}"),
+            SaveError::Parse(err) => write!(
+                f,
+                "could not parse save file at {}:{}: {}",
+                err.position.line, err.position.col, err.code
+            ),
+            SaveError::Write(err) => write!(f, "could not write save: {err}"),
+            SaveError::Version(version) => write!(f, "unsupported save version {version}"),
+            SaveError::Newer(version) => write!(f, "save version {version} is from a newer build"),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+impl From<std::io::Error> for SaveError {
+    fn from(err: std::io::Error) -> Self {
+        SaveError::Io(err)
+    }
+}
+
+impl From<SpannedError> for SaveError {
+    fn from(err: SpannedError) -> Self {
+        SaveError::Parse(err)
+    }
+}
+
+impl From<ron::Error> for SaveError {
+    fn from(err: ron::Error) -> Self {
+        SaveError::Write(err)
+    }
+}
+
+impl Save {
+    pub fn to_ron(progress: &Progress, settings: &Settings) -> Result<String, SaveError> {
+        let file = SaveFile {
+            version: SAVE_VERSION,
+            progress: progress.clone(),
+            settings: settings.clone(),
+        };
+        Ok(ron::ser::to_string_pretty(&file, ron::ser::PrettyConfig::default())?)
+    }
+
+    pub fn from_ron(text: &str) -> Result<Save, SaveError> {
+        let header: SaveHeader = ron::from_str(text)?;
+        // a save from an older layout gets an arm here that reads it and
+        // converts it to the current one
+        let file: SaveFile = match header.version {
+            SAVE_VERSION => ron::from_str(text)?,
+            version if version > SAVE_VERSION => return Err(SaveError::Newer(version)),
+            version => return Err(SaveError::Version(version)),
+        };
+        Ok(Save {
+            progress: file.progress,
+            settings: file.settings,
+        })
+    }
+}
+
+/// `name` in the game's folder under the platform's per-user data directory.
+pub fn data_file(name: &str) -> Option<PathBuf> {
+    Some(data_dir()?.join("crucible").join(name))
+}
+
+pub fn save_path() -> Option<PathBuf> {
+    data_file("save.ron")
+}
+
+#[cfg(target_os = "windows")]
+fn data_dir() -> Option<PathBuf> {
+    env::var_os("APPDATA").map(PathBuf::from)
+}
+
+#[cfg(target_os = "macos")]
+fn data_dir() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Application Support"))
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn data_dir() -> Option<PathBuf> {
+    env::var_os("XDG_DATA_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+}
+
+/// Reads the save at `path`. A first run starts from scratch; so does a
+/// save that can't be parsed, after it has been moved out of the way. `None`
+/// means the file has to be left as it is, so the session must not save.
+pub fn load_save(path: &Path) -> Option<Save> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Some(Save::default()),
+        Err(err) => {
+            error!("{}: {}; progress won't be saved", path.display(), SaveError::from(err));
+            return None;
+        }
+    };
+    match Save::from_ron(&text) {
+        Ok(save) => Some(save),
+        Err(err @ SaveError::Newer(_)) => {
+            warn!("{}: {err}; progress won't be saved", path.display());
+            None
+        }
+        Err(err) => {
+            let backup = path.with_extension("ron.bak");
+            warn!("{}: {err}; starting over, old save kept as {}", path.display(), backup.display());
+            if let Err(err) = fs::rename(path, &backup) {
+                error!("{}: {}", backup.display(), SaveError::from(err));
+            }
+            Some(Save::default())
+        }
+    }
+}
+
+/// Runs before anything else, once logging is up, so problems with the save
+/// get reported. A replay sets up each fight's progress itself, so nothing it
+/// does is saved.
+pub fn load_saved_game(mut commands: Commands, playback: Option<Res<Playback>>) {
+    let Some(path) = save_path() else {
+        return;
+    };
+    let Some(save) = load_save(&path) else {
+        return;
+    };
+    commands.insert_resource(save.progress);
+    commands.insert_resource(save.settings);
+    if playback.is_none() {
+        commands.insert_resource(SavePath(path));
+    }
+}
+
+/// Writes next to the save and swaps it in, so a crash mid-write can't
+/// leave half a file behind.
+pub fn write_save(path: Res<SavePath>, progress: Res<Progress>, settings: Res<Settings>) {
+    let path = &path.0;
+    let temp = path.with_extension("ron.tmp");
+    let written = Save::to_ron(&progress, &settings).and_then(|text| {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&temp, text)?;
+        Ok(fs::rename(&temp, path)?)
+    });
+    if let Err(err) = written {
+        error!("{}: {err}", path.display());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn save_round_trips_and_fills_in_missing_fields() {
+        let mut progress = Progress::default();
+        progress.record("fighters/abigail.fighter.ron".to_string(), 0, true, Some(42.0));
+        let text = Save::to_ron(&progress, &Settings::default()).unwrap();
+        let save = Save::from_ron(&text).unwrap();
+        assert_eq!(save.progress.beaten, 1);
+        assert_eq!(save.progress.records["fighters/abigail.fighter.ron"].best_ko, Some(42.0));
+
+        let save = Save::from_ron("(version: 1, progress: (beaten: 2))").unwrap();
+        assert_eq!(save.progress.beaten, 2);
+        assert_eq!(save.settings.rounds, Settings::default().rounds);
+        assert!(matches!(Save::from_ron("(version: 99)"), Err(SaveError::Newer(99))));
+    }
+
+    #[test]
+    fn save_without_version_loads_as_version_one() {
+        let save = Save::from_ron("(progress: (beaten: 2), settings: (rounds: 5))").unwrap();
+        assert_eq!(save.progress.beaten, 2);
+        assert_eq!(save.settings.rounds, 5);
+    }
+
+    #[test]
+    fn corrupt_save_is_moved_aside() {
+        let dir = std::env::temp_dir().join(format!("crucible-save-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("save.ron");
+        std::fs::write(&path, "(version: 1, progress: (beaten: ").unwrap();
+
+        let save = load_save(&path).unwrap();
+        assert_eq!(save.progress.beaten, 0);
+        assert!(!path.exists());
+        assert!(dir.join("save.ron.bak").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn newer_save_is_left_alone() {
+        let dir = std::env::temp_dir().join(format!("crucible-newer-save-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("save.ron");
+        let text = format!("(version: {}, progress: (beaten: 3))", SAVE_VERSION + 1);
+        std::fs::write(&path, &text).unwrap();
+
+        assert!(load_save(&path).is_none());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), text);
+        assert!(!dir.join("save.ron.bak").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
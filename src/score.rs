@@ -0,0 +1,97 @@
+//! Points for the player's side of the fight, and the numbers behind the
+//! breakdown on the victory screen. Scored from combat events inside the
+//! fixed tick, so a replay scores the same as the fight it recorded.
+
+use bevy::prelude::*;
+
+use crate::{
+    attack::{AttackEvent, DodgedEvent, HitEvent},
+    knockdown::KnockdownEvent,
+    player::Player,
+    round::{Finish, Round},
+};
+
+const HIT_POINTS: u32 = 100;
+/// On top of [`HIT_POINTS`], for catching the enemy mid-punch.
+const COUNTER_POINTS: u32 = 200;
+const DODGE_POINTS: u32 = 50;
+const KNOCKDOWN_POINTS: u32 = 1000;
+
+/// The player's tally for the current fight.
+#[derive(Resource, Default, Clone, Copy, PartialEq, Debug)]
+pub struct Score {
+    pub points: u32,
+    pub punches_thrown: u32,
+    pub punches_landed: u32,
+    pub counters: u32,
+    pub dodges: u32,
+    pub knockdowns: u32,
+    pub damage_dealt: u32,
+    pub damage_taken: u32,
+}
+
+impl Score {
+    /// Share of punches thrown that landed, from 0 to 1.
+    pub fn accuracy(&self) -> f32 {
+        if self.punches_thrown == 0 {
+            return 0.;
+        }
+        self.punches_landed as f32 / self.punches_thrown as f32
+    }
+
+    /// The lines shown on the victory screen.
+    pub fn breakdown(&self, round: &Round) -> String {
+        let finish_time = match round.result {
+            Some(result) if result.finish != Finish::Decision => {
+                let secs = round.elapsed as u32;
+                format!("{} TIME {}:{:02}", result.finish.label(), secs / 60, secs % 60)
+            }
+            _ => "WENT THE DISTANCE".to_string(),
+        };
+        [
+            format!("ACCURACY {:.0}%", self.accuracy() * 100.),
+            format!("COUNTERS {}", self.counters),
+            format!("DAMAGE DEALT {}", self.damage_dealt),
+            format!("DAMAGE TAKEN {}", self.damage_taken),
+            finish_time,
+            format!("SCORE {}", self.points),
+        ]
+        .join("\n")
+    }
+}
+
+pub fn update_score(
+    mut score: ResMut<Score>,
+    players: Query<(), With<Player>>,
+    mut attacks: EventReader<AttackEvent>,
+    mut hits: EventReader<HitEvent>,
+    mut dodges: EventReader<DodgedEvent>,
+    mut knockdowns: EventReader<KnockdownEvent>,
+) {
+    score.punches_thrown += attacks.read().filter(|attack| players.contains(attack.attacker)).count() as u32;
+    for hit in hits.read() {
+        if players.contains(hit.target) {
+            score.damage_taken += hit.damage;
+            continue;
+        }
+        score.punches_landed += 1;
+        score.damage_dealt += hit.damage;
+        score.points += HIT_POINTS;
+        if hit.counter {
+            score.counters += 1;
+            score.points += COUNTER_POINTS;
+        }
+    }
+    for dodge in dodges.read() {
+        if players.contains(dodge.target) {
+            score.dodges += 1;
+            score.points += DODGE_POINTS;
+        }
+    }
+    for knockdown in knockdowns.read() {
+        if !players.contains(knockdown.fighter) {
+            score.knockdowns += 1;
+            score.points += KNOCKDOWN_POINTS;
+        }
+    }
+}
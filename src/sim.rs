@@ -0,0 +1,609 @@
+//! Headless harness for the combat systems: a `MinimalPlugins` app with one
+//! player and one enemy, driven by scripted key presses and a manual clock.
+
+use std::time::Duration;
+
+use bevy::{prelude::*, time::TimeUpdateStrategy};
+
+use crate::{
+    attack::{BlockedEvent, DodgedEvent, HitEvent, WhiffEvent},
+    combat::CombatPlugin,
+    enemy::{Enemy, EnemyStates, EnemyStats},
+    pattern::{AttackPattern, PatternStep},
+    player::{Player, PlayerStates},
+    rng::CombatRng,
+    round::Round,
+    state::AppState,
+    timestep::{SimTranslation, TICK_HZ},
+    util::*,
+};
+
+pub const FRAME: f32 = 1.0 / TICK_HZ as f32;
+
+pub struct Sim {
+    pub app: App,
+}
+
+/// Running totals of every resolved punch.
+#[derive(Resource, Default, Clone, Copy, PartialEq, Debug)]
+pub struct Outcomes {
+    pub hits: usize,
+    pub blocks: usize,
+    pub dodges: usize,
+    pub whiffs: usize,
+}
+
+fn count_outcomes(
+    mut outcomes: ResMut<Outcomes>,
+    mut hits: EventReader<HitEvent>,
+    mut blocks: EventReader<BlockedEvent>,
+    mut dodges: EventReader<DodgedEvent>,
+    mut whiffs: EventReader<WhiffEvent>,
+) {
+    outcomes.hits += hits.read().count();
+    outcomes.blocks += blocks.read().count();
+    outcomes.dodges += dodges.read().count();
+    outcomes.whiffs += whiffs.read().count();
+}
+
+fn clip(first: usize, last: usize, frame_time: f32, loops: bool) -> SpriteClip {
+    SpriteClip {
+        indices: AnimationIndices { first, last },
+        frame_time,
+        loops,
+    }
+}
+
+impl Sim {
+    /// An enemy running `pattern`, or the random default when it is empty.
+    pub fn new(seed: u64, pattern: Vec<PatternStep>) -> Sim {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugins(CombatPlugin)
+            .insert_resource(CombatRng::new(seed))
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(
+                1.0 / TICK_HZ,
+            )))
+            .init_resource::<Input<KeyCode>>()
+            .init_resource::<Outcomes>()
+            .insert_resource(NextState(Some(AppState::Fighting)))
+            .add_systems(Update, count_outcomes);
+
+        let player_states = PlayerStates {
+            idle: clip(0, 1, 0.3, true),
+            punch: clip(2, 5, 0.03, false),
+            hit: clip(6, 7, 0.3, false),
+            block: clip(8, 9, 0.1, false),
+            dodge: clip(10, 11, 0.1, false),
+            death: clip(12, 12, 0.1, false),
+        };
+        let player_origin = Vec3::new(0.0, -80.0, 1.0);
+        app.world.spawn((
+            Player::new(player_origin, player_states),
+            SimTranslation::new(player_origin),
+            Transform::default(),
+            TextureAtlasSprite::new(0),
+        ));
+
+        let enemy_states = EnemyStates {
+            idle: clip(0, 1, 0.3, true),
+            punch_warning: clip(2, 3, 0.2, true),
+            punch: clip(4, 4, 0.3, false),
+            hit: clip(5, 5, 0.3, false),
+            block: clip(6, 6, 0.1, false),
+            death: clip(7, 7, 0.1, false),
+        };
+        app.world.spawn((
+            Enemy::new(Vec3::ZERO, enemy_states, AttackPattern::new(pattern), EnemyStats::default()),
+            SimTranslation::new(Vec3::ZERO),
+            Transform::default(),
+            TextureAtlasSprite::new(0),
+        ));
+
+        Sim { app }
+    }
+
+    /// Runs one frame, then clears this frame's presses the way `InputPlugin` would.
+    pub fn step(&mut self) {
+        self.app.update();
+        self.app.world.resource_mut::<Input<KeyCode>>().clear();
+    }
+
+    pub fn advance(&mut self, secs: f32) {
+        for _ in 0..(secs / FRAME).round() as u32 {
+            self.step();
+        }
+    }
+
+    pub fn hold(&mut self, key: KeyCode) {
+        self.app.world.resource_mut::<Input<KeyCode>>().press(key);
+    }
+
+    pub fn release(&mut self, key: KeyCode) {
+        self.app.world.resource_mut::<Input<KeyCode>>().release(key);
+    }
+
+    /// Presses `key` for a single frame.
+    pub fn tap(&mut self, key: KeyCode) {
+        self.hold(key);
+        self.step();
+        self.release(key);
+    }
+
+    pub fn player(&mut self) -> &Player {
+        self.app.world.query::<&Player>().single(&self.app.world)
+    }
+
+    pub fn enemy(&mut self) -> &Enemy {
+        self.app.world.query::<&Enemy>().single(&self.app.world)
+    }
+
+    pub fn player_mut(&mut self) -> Mut<'_, Player> {
+        self.app.world.query::<&mut Player>().single_mut(&mut self.app.world)
+    }
+
+    pub fn enemy_mut(&mut self) -> Mut<'_, Enemy> {
+        self.app.world.query::<&mut Enemy>().single_mut(&mut self.app.world)
+    }
+
+    pub fn round(&self) -> &Round {
+        self.app.world.resource::<Round>()
+    }
+
+    pub fn outcomes(&self) -> Outcomes {
+        *self.app.world.resource::<Outcomes>()
+    }
+
+    pub fn translations(&mut self) -> Vec<Vec3> {
+        self.app
+            .world
+            .query::<&SimTranslation>()
+            .iter(&self.app.world)
+            .map(|translation| translation.current)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        controls::{Action, Binding, Bindings},
+        fighter::{FighterState, Side},
+        player::MAX_HEARTS,
+        fighter_def::FighterRole,
+        impact::HitStop,
+        round::{FightResult, Finish, RoundPhase},
+        replay::{Playback, Recorder, Replay},
+        score::Score,
+        training::{FrameData, PunchCheck, Training, TrainingAction},
+    };
+
+    // Fighters open with the enemy mid-jab, so most scenarios hold block
+    // through the first second before doing anything else.
+    fn sim(pattern: Vec<PatternStep>) -> Sim {
+        let mut sim = Sim::new(0, pattern);
+        sim.hold(KeyCode::Down);
+        sim.advance(1.0);
+        sim.release(KeyCode::Down);
+        sim
+    }
+
+    #[test]
+    fn player_blocks_during_enemy_punch_and_takes_no_hit() {
+        let mut sim = Sim::new(0, vec![PatternStep::Punch(Side::Left), PatternStep::Wait(10.0)]);
+        sim.hold(KeyCode::Down);
+        sim.advance(3.0);
+
+        assert_eq!(sim.player().fighter.hits_taken_total, 0);
+        // the opening jab and the authored punch
+        assert_eq!(sim.outcomes().blocks, 2);
+    }
+
+    #[test]
+    fn unblocked_enemy_punch_hits_player() {
+        let mut sim = sim(vec![PatternStep::Wait(0.5), PatternStep::Punch(Side::Left), PatternStep::Wait(10.0)]);
+        sim.advance(2.5);
+
+        assert_eq!(sim.player().fighter.hits_taken_total, 1);
+    }
+
+    #[test]
+    fn dodge_avoids_enemy_punch() {
+        let mut sim = sim(vec![PatternStep::Wait(0.5), PatternStep::Punch(Side::Left), PatternStep::Wait(10.0)]);
+        // the wind-up runs for a second; slip just before the punch lands
+        sim.advance(0.9);
+        sim.tap(KeyCode::Left);
+        sim.advance(1.5);
+
+        assert_eq!(sim.player().fighter.hits_taken_total, 0);
+        assert_eq!(sim.outcomes().dodges, 1);
+    }
+
+    #[test]
+    fn dodge_returns_player_to_idle_at_origin() {
+        let mut sim = sim(vec![PatternStep::Wait(10.0)]);
+        sim.tap(KeyCode::Right);
+        sim.advance(0.4);
+        assert_eq!(sim.player().fighter.state, FighterState::Dodging(Side::Right));
+
+        sim.advance(0.5);
+        assert_eq!(sim.player().fighter.state, FighterState::Idle);
+        assert_eq!(sim.player().fighter.translation(), Vec3::new(0.0, -80.0, 1.0));
+    }
+
+    #[test]
+    fn player_punch_hits_idle_enemy() {
+        let mut sim = sim(vec![PatternStep::Wait(10.0)]);
+        sim.tap(KeyCode::Z);
+        sim.advance(0.5);
+
+        assert_eq!(sim.enemy().fighter.hits_taken_total, 1);
+    }
+
+    #[test]
+    fn counters_score_more_than_plain_hits() {
+        let mut sim = sim(vec![PatternStep::Wait(0.5), PatternStep::Punch(Side::Left), PatternStep::Wait(10.0)]);
+        sim.tap(KeyCode::Z);
+        sim.advance(0.5);
+        let plain = *sim.app.world.resource::<Score>();
+        assert_eq!((plain.punches_thrown, plain.punches_landed, plain.counters), (1, 1, 0));
+
+        // caught mid wind-up
+        sim.advance(0.3);
+        assert!(matches!(sim.enemy().fighter.state, FighterState::WindUp(_)));
+        sim.tap(KeyCode::Z);
+        sim.advance(0.5);
+        sim.tap(KeyCode::X);
+        sim.advance(0.5);
+        let score = *sim.app.world.resource::<Score>();
+        assert_eq!((score.punches_thrown, score.punches_landed, score.counters), (3, 2, 1));
+        assert!(score.points - plain.points > plain.points);
+        assert_eq!(score.accuracy(), 2. / 3.);
+        assert_eq!(score.damage_dealt, 2 * plain.damage_dealt);
+    }
+
+    #[test]
+    fn training_keeps_everyone_up_and_reports_the_last_punch() {
+        let mut sim = sim(vec![PatternStep::Wait(10.0)]);
+        sim.app.insert_resource(Training { action: TrainingAction::PunchLeftOnly });
+        sim.app.init_resource::<FrameData>();
+        let clock = sim.round().timer;
+        sim.advance(8.0);
+        let player = &sim.player().fighter;
+        assert!(player.hits_taken_total >= 3);
+        assert_eq!(player.health, player.max_health);
+        assert_eq!(sim.round().timer, clock);
+
+        sim.tap(KeyCode::Z);
+        sim.advance(0.5);
+        assert!(matches!(
+            sim.app.world.resource::<FrameData>().last_punch,
+            Some(PunchCheck::Landed { tick, window }) if tick <= window
+        ));
+
+        sim.app.insert_resource(Training { action: TrainingAction::BlockOnly });
+        sim.advance(3.0);
+        assert_eq!(sim.enemy().fighter.state, FighterState::Blocking);
+        sim.tap(KeyCode::Z);
+        sim.advance(0.5);
+        assert!(matches!(sim.app.world.resource::<FrameData>().last_punch, Some(PunchCheck::Blocked { .. })));
+    }
+
+    #[test]
+    fn clean_hit_freezes_the_fight_for_the_hit_stop() {
+        let mut sim = sim(vec![PatternStep::Wait(10.0)]);
+        sim.app.world.resource_mut::<HitStop>().frames = 6;
+        sim.tap(KeyCode::Z);
+        while sim.outcomes().hits == 0 {
+            sim.step();
+        }
+        let elapsed = sim.round().elapsed;
+        let animation = sim.enemy().fighter.animator.timer.0.elapsed();
+        for _ in 0..6 {
+            sim.step();
+            assert_eq!(sim.round().elapsed, elapsed);
+            assert_eq!(sim.enemy().fighter.animator.timer.0.elapsed(), animation);
+        }
+        sim.step();
+        assert!(sim.round().elapsed > elapsed);
+        assert_ne!(sim.enemy().fighter.animator.timer.0.elapsed(), animation);
+    }
+
+    #[test]
+    fn rebound_key_replaces_default_punch() {
+        let mut sim = sim(vec![PatternStep::Wait(10.0)]);
+        sim.app.insert_resource(Bindings::from_ron("{ PunchLeft: [Key(A)] }").unwrap());
+        sim.tap(KeyCode::Z);
+        sim.advance(0.5);
+        assert_eq!(sim.enemy().fighter.hits_taken_total, 0);
+
+        sim.tap(KeyCode::A);
+        sim.advance(0.5);
+        assert_eq!(sim.enemy().fighter.hits_taken_total, 1);
+        // actions the file leaves out keep their defaults
+        assert!(sim.app.world.resource::<Bindings>().0[&Action::PunchRight].contains(&Binding::Key(KeyCode::X)));
+        // the defaults written out on first run read back the same
+        let defaults = Bindings::default();
+        assert_eq!(Bindings::from_ron(&defaults.to_ron().unwrap()).unwrap().0, defaults.0);
+    }
+
+    #[test]
+    fn enemy_block_stops_player_punch() {
+        let mut sim = sim(vec![PatternStep::Block, PatternStep::Wait(10.0)]);
+        sim.tap(KeyCode::X);
+        sim.advance(0.5);
+
+        assert_eq!(sim.enemy().fighter.hits_taken_total, 0);
+        assert_eq!(sim.outcomes(), Outcomes { hits: 0, blocks: 2, dodges: 0, whiffs: 0 });
+    }
+
+    #[test]
+    fn punch_with_nobody_to_land_on_whiffs_once() {
+        let mut sim = sim(vec![PatternStep::Wait(10.0)]);
+        let before = sim.outcomes();
+        let enemy = sim.app.world.query_filtered::<Entity, With<Enemy>>().single(&sim.app.world);
+        sim.app.world.despawn(enemy);
+        sim.tap(KeyCode::Z);
+        sim.advance(0.5);
+
+        assert_eq!(sim.outcomes(), Outcomes { whiffs: before.whiffs + 1, ..before });
+    }
+
+    #[test]
+    fn blocked_punches_drain_hearts_until_tired() {
+        let mut sim = sim(vec![PatternStep::Block]);
+        for _ in 0..MAX_HEARTS {
+            sim.tap(KeyCode::Z);
+            sim.advance(0.6);
+        }
+        assert_eq!(sim.player().hearts, 0);
+        assert!(sim.player().is_tired());
+
+        sim.tap(KeyCode::Z);
+        assert_eq!(sim.player().fighter.state, FighterState::Idle);
+    }
+
+    #[test]
+    fn player_is_counted_out_after_going_down() {
+        let mut sim = sim(vec![PatternStep::Punch(Side::Left), PatternStep::Wait(0.5)]);
+        sim.advance(12.0);
+        assert_eq!(sim.player().fighter.health, 0);
+        assert_eq!(sim.player().fighter.state, FighterState::Down);
+        assert_eq!(sim.round().phase, RoundPhase::Knockdown);
+
+        sim.advance(10.0);
+        assert_eq!(sim.player().fighter.state, FighterState::KO);
+        assert_eq!(
+            sim.round().result,
+            Some(FightResult { winner: FighterRole::Enemy, finish: Finish::KO })
+        );
+    }
+
+    #[test]
+    fn mashing_gets_player_up_with_less_health() {
+        let mut sim = sim(vec![PatternStep::Wait(30.0)]);
+        sim.player_mut().fighter.health = 0;
+        sim.step();
+        assert_eq!(sim.round().phase, RoundPhase::Knockdown);
+
+        for _ in 0..8 {
+            sim.tap(KeyCode::Z);
+            sim.step();
+        }
+        assert_eq!(sim.round().phase, RoundPhase::Fighting);
+        assert_eq!(sim.player().fighter.state, FighterState::Idle);
+        assert_eq!(sim.player().fighter.health, 48);
+    }
+
+    #[test]
+    fn third_knockdown_in_a_round_is_a_tko() {
+        let mut sim = sim(vec![PatternStep::Wait(30.0)]);
+        sim.enemy_mut().stats.get_up_chance = 1.0;
+        for health in [48, 32] {
+            sim.enemy_mut().fighter.health = 0;
+            sim.advance(10.0);
+            assert_eq!(sim.round().phase, RoundPhase::Fighting);
+            assert_eq!(sim.enemy().fighter.health, health);
+        }
+
+        sim.enemy_mut().fighter.health = 0;
+        sim.step();
+        assert_eq!(sim.enemy().fighter.state, FighterState::KO);
+        assert_eq!(
+            sim.round().result,
+            Some(FightResult { winner: FighterRole::Player, finish: Finish::TKO })
+        );
+    }
+
+    #[test]
+    fn bell_freezes_fighters_until_next_round() {
+        // the opening jab lands, leaving something to recover between rounds
+        let mut sim = Sim::new(0, vec![PatternStep::Wait(10.0)]);
+        sim.app.insert_resource(Round::new(2, 2.0));
+        sim.advance(2.1);
+        assert_eq!(sim.round().phase, RoundPhase::Bell);
+        assert_eq!(sim.player().fighter.health, 80);
+
+        sim.tap(KeyCode::Left);
+        assert_eq!(sim.player().fighter.state, FighterState::Idle);
+
+        sim.advance(2.0);
+        assert_eq!(sim.round().phase, RoundPhase::Intermission);
+        assert_eq!(sim.player().fighter.health, 88);
+
+        sim.advance(5.0);
+        assert_eq!(sim.round().phase, RoundPhase::Fighting);
+        assert_eq!(sim.round().number, 2);
+    }
+
+    #[test]
+    fn fight_is_over_after_last_round() {
+        let mut sim = sim(vec![PatternStep::Wait(10.0)]);
+        sim.app.insert_resource(Round::new(1, 1.0));
+        sim.advance(3.5);
+
+        assert_eq!(sim.round().phase, RoundPhase::Over);
+    }
+
+    #[test]
+    fn combat_only_runs_while_fighting() {
+        let mut sim = sim(vec![PatternStep::Wait(10.0)]);
+        sim.app.insert_resource(NextState(Some(AppState::Paused)));
+        let clock = sim.round().timer;
+        let state = sim.player().fighter.state;
+        sim.tap(KeyCode::Z);
+        sim.advance(1.0);
+        assert_eq!(sim.round().timer, clock);
+        assert_eq!(sim.player().fighter.state, state);
+
+        sim.app.insert_resource(NextState(Some(AppState::Fighting)));
+        sim.advance(1.0);
+        assert!(sim.round().timer < clock);
+    }
+
+    #[test]
+    fn pausing_does_not_leak_time_into_the_fight() {
+        let pattern = || vec![PatternStep::Punch(Side::Left), PatternStep::Wait(0.7)];
+        let mut reference = sim(pattern());
+        let mut paused = sim(pattern());
+        for sim in [&mut reference, &mut paused] {
+            sim.tap(KeyCode::Z);
+            sim.advance(0.45);
+        }
+
+        paused.app.insert_resource(NextState(Some(AppState::Paused)));
+        paused.step();
+        let virtual_clock = paused.app.world.resource::<Time<Virtual>>().elapsed();
+        paused.advance(2.0);
+        assert_eq!(paused.app.world.resource::<Time<Virtual>>().elapsed(), virtual_clock);
+        paused.app.insert_resource(NextState(Some(AppState::Fighting)));
+
+        reference.advance(1.5);
+        // the frames either side of the pause don't tick; line up on fight time
+        while paused.round().elapsed < reference.round().elapsed {
+            paused.step();
+        }
+        assert_eq!(paused.round().elapsed, reference.round().elapsed);
+        assert_eq!(paused.round().timer, reference.round().timer);
+        assert_eq!(paused.player().fighter.state, reference.player().fighter.state);
+        assert_eq!(paused.enemy().fighter.state, reference.enemy().fighter.state);
+        assert_eq!(paused.outcomes(), reference.outcomes());
+        assert_eq!(paused.translations(), reference.translations());
+    }
+
+    #[test]
+    fn same_seed_replays_same_fight() {
+        let mut runs = [Sim::new(1234, vec![]), Sim::new(1234, vec![])];
+        for sim in &mut runs {
+            for _ in 0..10 {
+                sim.tap(KeyCode::Z);
+                sim.advance(0.4);
+                sim.tap(KeyCode::Left);
+                sim.advance(0.6);
+            }
+        }
+        let [a, b] = &mut runs;
+
+        assert_eq!(a.player().fighter.hits_taken_total, b.player().fighter.hits_taken_total);
+        assert_eq!(a.enemy().fighter.hits_taken_total, b.enemy().fighter.hits_taken_total);
+        assert_eq!(a.outcomes(), b.outcomes());
+        assert_eq!(a.translations(), b.translations());
+    }
+
+    #[test]
+    fn replay_reproduces_recorded_fight() {
+        let mut recorded = Sim::new(99, vec![]);
+        recorded.app.insert_resource(Recorder {
+            path: None,
+            replay: Replay::new(99),
+        });
+        for _ in 0..8 {
+            recorded.tap(KeyCode::X);
+            recorded.advance(0.3);
+            recorded.hold(KeyCode::Down);
+            recorded.advance(0.5);
+            recorded.release(KeyCode::Down);
+            recorded.tap(KeyCode::Right);
+            recorded.advance(0.8);
+        }
+        let text = recorded.app.world.resource::<Recorder>().replay.to_ron().unwrap();
+        let replay = Replay::from_ron(&text).unwrap();
+        let frames = replay.inputs.len();
+
+        let mut replayed = Sim::new(replay.seed, vec![]);
+        replayed.app.insert_resource(Playback::new(replay));
+        for _ in 0..frames {
+            replayed.step();
+        }
+
+        assert_eq!(recorded.player().fighter.hits_taken_total, replayed.player().fighter.hits_taken_total);
+        assert_eq!(recorded.enemy().fighter.hits_taken_total, replayed.enemy().fighter.hits_taken_total);
+        assert_eq!(recorded.translations(), replayed.translations());
+    }
+
+    /// Goes through `PreFight` the way the game does, so a recorder or a
+    /// playback sets the fight up.
+    fn start_fight(sim: &mut Sim) {
+        sim.app.insert_resource(NextState(Some(AppState::PreFight)));
+        sim.step();
+        sim.app.insert_resource(NextState(Some(AppState::Fighting)));
+    }
+
+    #[test]
+    fn replay_brings_its_own_round_settings() {
+        let mut recorded = Sim::new(5, vec![]);
+        recorded.app.insert_resource(Recorder {
+            path: None,
+            replay: Replay::new(5),
+        });
+        recorded.app.insert_resource(Round::new(2, 1.5));
+        start_fight(&mut recorded);
+        for _ in 0..10 {
+            recorded.tap(KeyCode::Z);
+            recorded.advance(0.5);
+            recorded.tap(KeyCode::X);
+            recorded.advance(0.7);
+        }
+        assert_eq!(recorded.round().number, 2);
+        assert!(recorded.round().result.is_some());
+
+        let text = recorded.app.world.resource::<Recorder>().replay.to_ron().unwrap();
+        let replay = Replay::from_ron(&text).unwrap();
+        assert_eq!((replay.rounds, replay.round_length), (2, 1.5));
+        let frames = replay.inputs.len();
+
+        // left on the default three rounds of three minutes
+        let mut replayed = Sim::new(replay.seed, vec![]);
+        replayed.app.insert_resource(Playback::new(replay));
+        start_fight(&mut replayed);
+        for _ in 0..frames {
+            replayed.step();
+        }
+
+        let (recorded_round, replayed_round) = (recorded.round().clone(), replayed.round().clone());
+        assert_eq!((replayed_round.count, replayed_round.length), (2, 1.5));
+        assert_eq!(recorded_round.number, replayed_round.number);
+        assert_eq!(recorded_round.phase, replayed_round.phase);
+        assert_eq!(recorded_round.result, replayed_round.result);
+        assert_eq!(recorded.enemy().fighter.hits_taken_total, replayed.enemy().fighter.hits_taken_total);
+        assert_eq!(recorded.translations(), replayed.translations());
+    }
+
+    #[test]
+    fn animator_loops_only_when_asked() {
+        let mut looping = Animator::from_clip(clip(0, 1, 0.1, true));
+        let mut once = Animator::from_clip(clip(0, 1, 0.1, false));
+        for _ in 0..3 {
+            looping.tick(Duration::from_secs_f32(0.1));
+            once.tick(Duration::from_secs_f32(0.1));
+        }
+
+        assert_eq!(looping.index, 1);
+        assert_eq!(once.index, 1);
+        looping.tick(Duration::from_secs_f32(0.1));
+        once.tick(Duration::from_secs_f32(0.1));
+        assert_eq!(looping.index, 0);
+        assert_eq!(once.index, 1);
+    }
+}
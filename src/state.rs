@@ -0,0 +1,165 @@
+//! Top-level flow of the game. Each state spawns its own screen on enter and
+//! despawns it on exit; everything that belongs to a fight is tagged with
+//! [`FightEntity`] and lives from `PreFight` until the result screen closes.
+
+use bevy::prelude::*;
+
+use crate::{
+    controls::{Action, ActionState, PlayerInput},
+    fighter_def::FighterRole,
+    password,
+    roster::Progress,
+    round::{Round, RoundPhase},
+    score::Score,
+};
+
+#[derive(States, Default, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum AppState {
+    #[default]
+    Loading,
+    Title,
+    /// Typing in a password to pick up a career.
+    Password,
+    /// The roster, with records and the next challenger.
+    Circuit,
+    /// Fighters are in the ring, waiting for the opening bell.
+    PreFight,
+    Fighting,
+    Paused,
+    Victory,
+    Defeat,
+}
+
+/// Part of the current fight: stage, fighters and HUD.
+#[derive(Component)]
+pub struct FightEntity;
+
+/// Part of the screen shown in the current state.
+#[derive(Component)]
+pub struct ScreenEntity;
+
+/// Counts down the time spent on a screen that moves on by itself.
+#[derive(Resource)]
+pub struct ScreenTimer(pub Timer);
+
+const PRE_FIGHT_DURATION: f32 = 2.0;
+
+pub fn despawn_all<T: Component>(mut commands: Commands, query: Query<Entity, With<T>>) {
+    for entity in &query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+pub fn spawn_screen(commands: &mut Commands, asset_server: &AssetServer, message: String) -> Entity {
+    commands
+        .spawn((
+            Text2dBundle {
+                text: Text::from_section(
+                    message,
+                    TextStyle {
+                        font: asset_server.load("fonts/font.ttf"),
+                        font_size: 16.0,
+                        color: Color::WHITE,
+                    },
+                )
+                .with_alignment(TextAlignment::Center),
+                transform: Transform::from_translation(Vec3::new(0.0, 0.0, 10.0)),
+                ..default()
+            },
+            ScreenEntity,
+        ))
+        .id()
+}
+
+pub fn spawn_title(mut commands: Commands, asset_server: Res<AssetServer>) {
+    spawn_screen(&mut commands, &asset_server, "CRUCIBLE\n\n\nPRESS ENTER\n\nP FOR PASSWORD\nT FOR TRAINING".to_string());
+}
+
+pub fn spawn_pre_fight(mut commands: Commands, asset_server: Res<AssetServer>) {
+    spawn_screen(&mut commands, &asset_server, "ROUND 1\n\nFIGHT!".to_string());
+    commands.insert_resource(ScreenTimer(Timer::from_seconds(PRE_FIGHT_DURATION, TimerMode::Once)));
+}
+
+/// After a win this also shows how the fight went and the password for the
+/// career so far.
+pub fn spawn_result(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    round: Res<Round>,
+    score: Res<Score>,
+    progress: Res<Progress>,
+    state: Res<State<AppState>>,
+) {
+    let (headline, details) = match state.get() {
+        AppState::Victory => (
+            "YOU WIN!",
+            format!("\n\n{}\n\nPASSWORD\n{}", score.breakdown(&round), password::encode(&progress)),
+        ),
+        _ => ("YOU LOSE", String::new()),
+    };
+    let finish = round.result.map(|result| result.finish.label()).unwrap_or_default();
+    spawn_screen(
+        &mut commands,
+        &asset_server,
+        format!("{headline}\n\nBY {finish}{details}\n\n\nPRESS ENTER"),
+    );
+}
+
+pub fn press_enter(
+    actions: Res<ActionState>,
+    state: Res<State<AppState>>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if !actions.just_pressed(Action::Confirm) {
+        return;
+    }
+    match state.get() {
+        AppState::Title => next_state.set(AppState::Circuit),
+        AppState::Circuit => next_state.set(AppState::PreFight),
+        AppState::Victory | AppState::Defeat => next_state.set(AppState::Circuit),
+        _ => {}
+    }
+}
+
+pub fn count_down_pre_fight(
+    time: Res<Time>,
+    mut timer: ResMut<ScreenTimer>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if timer.0.tick(time.delta()).just_finished() {
+        next_state.set(AppState::Fighting);
+    }
+}
+
+pub fn toggle_pause(
+    actions: Res<ActionState>,
+    state: Res<State<AppState>>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if !actions.just_pressed(Action::Pause) {
+        return;
+    }
+    match state.get() {
+        AppState::Fighting => next_state.set(AppState::Paused),
+        AppState::Paused => next_state.set(AppState::Fighting),
+        _ => {}
+    }
+}
+
+/// Drops presses made while paused so they don't fire on resume.
+pub fn clear_player_input(mut input: ResMut<PlayerInput>) {
+    *input = PlayerInput::default();
+}
+
+/// Moves to the result screen once the final result has been on show.
+pub fn end_fight(round: Res<Round>, mut next_state: ResMut<NextState<AppState>>) {
+    let Some(result) = round.result else {
+        return;
+    };
+    if round.phase == RoundPhase::Over && round.timer <= 0. {
+        next_state.set(match result.winner {
+            FighterRole::Player => AppState::Victory,
+            FighterRole::Enemy => AppState::Defeat,
+        });
+    }
+}
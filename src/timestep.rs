@@ -0,0 +1,40 @@
+//! Combat runs in [`FixedUpdate`](bevy::app::FixedUpdate) at [`TICK_HZ`] so
+//! timers and hit windows behave the same at any frame rate. Fighters move a
+//! [`SimTranslation`] on each tick and their `Transform` is blended between
+//! the last two ticks when drawn.
+
+use bevy::prelude::*;
+
+pub const TICK_HZ: f64 = 60.0;
+
+#[derive(Component, Default)]
+pub struct SimTranslation {
+    pub previous: Vec3,
+    pub current: Vec3,
+}
+
+impl SimTranslation {
+    pub fn new(translation: Vec3) -> SimTranslation {
+        SimTranslation {
+            previous: translation,
+            current: translation,
+        }
+    }
+}
+
+/// Runs first in every tick so `previous` holds where the last tick left off.
+pub fn begin_tick(mut query: Query<&mut SimTranslation>) {
+    for mut translation in &mut query {
+        translation.previous = translation.current;
+    }
+}
+
+pub fn interpolate_translation(
+    time: Res<Time<Fixed>>,
+    mut query: Query<(&SimTranslation, &mut Transform)>,
+) {
+    let alpha = time.overstep_percentage();
+    for (translation, mut transform) in &mut query {
+        transform.translation = translation.previous.lerp(translation.current, alpha);
+    }
+}
@@ -0,0 +1,220 @@
+//! Training mode, for learning an opponent's tells. Nobody's health or
+//! hearts run out, the round clock stands still, the enemy sticks to one
+//! kind of action picked from the pause menu, and an overlay shows the
+//! enemy's state with the frames it has left and how the player's last punch
+//! went. Started with T on the title screen; the fight counts for nothing.
+
+use bevy::{prelude::*, sprite::Anchor};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    attack::{AttackEvent, BlockedEvent, HitEvent, WhiffEvent},
+    controls::{Action, ActionState},
+    enemy::Enemy,
+    fighter::{FighterState, Side},
+    pattern::{AttackPattern, PatternStep},
+    player::{Player, MAX_HEARTS},
+    state::{AppState, FightEntity},
+    timestep::TICK_HZ,
+};
+
+/// What the enemy does in training.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default, Debug)]
+pub enum TrainingAction {
+    /// The dice-roll pattern fighters without one of their own use.
+    #[default]
+    Random,
+    BlockOnly,
+    PunchLeftOnly,
+}
+
+impl TrainingAction {
+    pub fn label(self) -> &'static str {
+        match self {
+            TrainingAction::Random => "RANDOM",
+            TrainingAction::BlockOnly => "BLOCK ONLY",
+            TrainingAction::PunchLeftOnly => "PUNCH LEFT ONLY",
+        }
+    }
+
+    /// The next action in the menu, wrapping around.
+    pub fn cycle(self, forward: bool) -> TrainingAction {
+        const ORDER: [TrainingAction; 3] = [TrainingAction::Random, TrainingAction::BlockOnly, TrainingAction::PunchLeftOnly];
+        let index = ORDER.iter().position(|action| *action == self).unwrap_or(0);
+        let step = if forward { 1 } else { ORDER.len() - 1 };
+        ORDER[(index + step) % ORDER.len()]
+    }
+
+    fn pattern(self) -> AttackPattern {
+        match self {
+            TrainingAction::Random => AttackPattern::default(),
+            TrainingAction::BlockOnly => AttackPattern::new(vec![PatternStep::Block]),
+            TrainingAction::PunchLeftOnly => AttackPattern::new(vec![PatternStep::Wait(1.0), PatternStep::Punch(Side::Left)]),
+        }
+    }
+}
+
+/// Present while in training mode.
+#[derive(Resource, Default, Debug)]
+pub struct Training {
+    pub action: TrainingAction,
+}
+
+/// How the player's last punch went, counting ticks from the one it was
+/// thrown on as 1.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PunchCheck {
+    Pending,
+    /// Resolved on this tick of a window that many ticks long.
+    Landed { tick: u32, window: u32 },
+    Blocked { tick: u32, window: u32 },
+    /// The window closed with nothing to land on.
+    Missed { window: u32 },
+}
+
+/// Filled in every tick while training.
+#[derive(Resource, Default, Debug)]
+pub struct FrameData {
+    pub last_punch: Option<PunchCheck>,
+    /// Ticks since the last punch was thrown, and its window.
+    since_thrown: u32,
+    window: u32,
+}
+
+#[derive(Component)]
+pub struct FrameDataText;
+
+fn ticks(secs: f32) -> u32 {
+    (secs * TICK_HZ as f32).round() as u32
+}
+
+pub fn open_training(actions: Res<ActionState>, mut commands: Commands, mut next_state: ResMut<NextState<AppState>>) {
+    if actions.just_pressed(Action::Training) {
+        commands.init_resource::<Training>();
+        commands.init_resource::<FrameData>();
+        next_state.set(AppState::PreFight);
+    }
+}
+
+pub fn end_training(mut commands: Commands) {
+    commands.remove_resource::<Training>();
+    commands.remove_resource::<FrameData>();
+}
+
+/// Nobody goes down in training; runs after punches land and before anyone
+/// is checked for a knockdown.
+pub fn refill_health(mut players: Query<&mut Player>, mut enemies: Query<&mut Enemy>) {
+    for mut player in &mut players {
+        player.fighter.health = player.fighter.max_health;
+        player.hearts = MAX_HEARTS;
+    }
+    for mut enemy in &mut enemies {
+        enemy.fighter.health = enemy.fighter.max_health;
+    }
+}
+
+/// Sets the enemy's pattern when the choice changes or a new enemy appears.
+/// An enemy standing around cuts the wait short to start on it.
+pub fn apply_training_action(training: Res<Training>, mut enemies: Query<&mut Enemy>) {
+    for mut enemy in &mut enemies {
+        if !training.is_changed() && !enemy.is_added() {
+            continue;
+        }
+        enemy.set_pattern(training.action.pattern());
+        if enemy.fighter.state == FighterState::Idle {
+            enemy.fighter.timer = 0.;
+        }
+    }
+}
+
+pub fn update_frame_data(
+    mut frame_data: ResMut<FrameData>,
+    players: Query<(), With<Player>>,
+    mut attacks: EventReader<AttackEvent>,
+    mut hits: EventReader<HitEvent>,
+    mut blocks: EventReader<BlockedEvent>,
+    mut whiffs: EventReader<WhiffEvent>,
+) {
+    frame_data.since_thrown += 1;
+    for attack in attacks.read().filter(|attack| players.contains(attack.attacker)) {
+        frame_data.since_thrown = 0;
+        frame_data.window = ticks(attack.window);
+        frame_data.last_punch = Some(PunchCheck::Pending);
+    }
+    // read everything either way, so nothing stale is left for the next punch
+    let landed = hits.read().any(|hit| players.contains(hit.attacker));
+    let blocked = blocks.read().any(|block| players.contains(block.attacker));
+    let missed = whiffs.read().any(|whiff| players.contains(whiff.attacker));
+    if frame_data.last_punch != Some(PunchCheck::Pending) {
+        return;
+    }
+    let (tick, window) = (frame_data.since_thrown + 1, frame_data.window);
+    if landed {
+        frame_data.last_punch = Some(PunchCheck::Landed { tick, window });
+    } else if blocked {
+        frame_data.last_punch = Some(PunchCheck::Blocked { tick, window });
+    } else if missed {
+        frame_data.last_punch = Some(PunchCheck::Missed { window });
+    }
+}
+
+pub fn spawn_frame_data(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn((
+        Text2dBundle {
+            text: Text::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/font.ttf"),
+                    font_size: 16.0,
+                    color: Color::WHITE,
+                },
+            ),
+            text_anchor: Anchor::TopLeft,
+            transform: Transform::from_translation(Vec3::new(-240.0, 140.0, 4.0)),
+            ..default()
+        },
+        FrameDataText,
+        FightEntity,
+    ));
+}
+
+fn state_label(state: FighterState) -> String {
+    let side = |side: Side| if side == Side::Left { "L" } else { "R" };
+    match state {
+        FighterState::Idle => "IDLE".to_string(),
+        FighterState::Blocking => "BLOCK".to_string(),
+        FighterState::Dodging(s) => format!("DODGE {}", side(s)),
+        FighterState::WindUp(s) => format!("WIND-UP {}", side(s)),
+        FighterState::Punching(s) => format!("PUNCH {}", side(s)),
+        FighterState::Hit => "HIT".to_string(),
+        FighterState::Down => "DOWN".to_string(),
+        FighterState::KO => "KO".to_string(),
+    }
+}
+
+pub fn update_frame_data_text(
+    training: Res<Training>,
+    frame_data: Res<FrameData>,
+    enemies: Query<&Enemy>,
+    mut text_query: Query<&mut Text, With<FrameDataText>>,
+) {
+    let Ok(enemy) = enemies.get_single() else {
+        return;
+    };
+    let last_punch = match frame_data.last_punch {
+        None => "-".to_string(),
+        Some(PunchCheck::Pending) => "...".to_string(),
+        Some(PunchCheck::Landed { tick, window }) => format!("HIT {tick}/{window}F"),
+        Some(PunchCheck::Blocked { tick, window }) => format!("BLOCKED {tick}/{window}F"),
+        Some(PunchCheck::Missed { window }) => format!("MISSED {window}F"),
+    };
+    let value = format!(
+        "TRAINING: {}\nENEMY {}\nTIMER {}F\nLAST PUNCH {last_punch}",
+        training.action.label(),
+        state_label(enemy.fighter.state),
+        ticks(enemy.fighter.timer.max(0.)),
+    );
+    for mut text in &mut text_query {
+        text.sections[0].value = value.clone();
+    }
+}
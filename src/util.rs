@@ -1,8 +1,10 @@
 use std::time::Duration;
 
 use bevy::{
+    ecs::component::Component,
     math::Rect,
-    sprite::TextureAtlasSprite, ecs::component::Component, prelude::{Deref, DerefMut}, time::Timer,
+    prelude::{Deref, DerefMut},
+    time::{Timer, TimerMode},
 };
 
 #[derive(Component, Clone, Copy)]
@@ -20,6 +22,13 @@ impl PartialEq for AnimationIndices {
 #[derive(Component, Deref, DerefMut)]
 pub struct AnimationTimer(pub Timer);
 
+/// A range of atlas frames together with how fast and whether it loops.
+#[derive(Clone, Copy, PartialEq)]
+pub struct SpriteClip {
+    pub indices: AnimationIndices,
+    pub frame_time: f32,
+    pub loops: bool,
+}
 
 pub struct Animator {
     pub index: usize,
@@ -35,14 +44,22 @@ impl Animator {
         loops: bool,
     ) -> Animator {
         Animator {
-            index: 0,
-            timer: timer,
-            indices: indices,
-            loops: loops,
+            index: indices.first,
+            timer,
+            indices,
+            loops,
         }
     }
 
-    pub fn tick(self: &mut Self, delta: Duration) {
+    pub fn from_clip(clip: SpriteClip) -> Animator {
+        Animator::new(
+            AnimationTimer(Timer::from_seconds(clip.frame_time, TimerMode::Repeating)),
+            clip.indices,
+            clip.loops,
+        )
+    }
+
+    pub fn tick(&mut self, delta: Duration) {
         self.timer.tick(delta);
         if self.timer.just_finished() {
             if self.index == self.indices.last {
@@ -55,11 +72,11 @@ impl Animator {
         }
     }
 
-    pub fn set_frametime(self: &mut Self, secs: f32) {
+    pub fn set_frametime(&mut self, secs: f32) {
         self.timer.set_duration(Duration::from_secs_f32(secs));
     }
 
-    pub fn set_indices(self: &mut Self, indices: AnimationIndices) {
+    pub fn set_indices(&mut self, indices: AnimationIndices) {
         if indices != self.indices {
             self.indices = indices;
             self.index = indices.first;
@@ -67,11 +84,11 @@ impl Animator {
         }
     }
 
-    pub fn set_indices_from_bounds(self: &mut Self, first: usize, last: usize) {
-        self.indices = AnimationIndices {
-            first: first,
-            last: last,
-        };
+    /// Switches to `clip`, restarting it only if it isn't already playing.
+    pub fn play(&mut self, clip: SpriteClip) {
+        self.set_indices(clip.indices);
+        self.set_frametime(clip.frame_time);
+        self.loops = clip.loops;
     }
 }
 